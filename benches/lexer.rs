@@ -0,0 +1,80 @@
+//! Whole-file lexing throughput.
+//!
+//!     cargo bench --bench lexer         # >= 100 KB per input, reports MB/s
+//!     cargo test --bench lexer          # smoke run on small inputs
+
+use std::env;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use mycc::lexer::{Lexer, Token};
+
+const BENCH_SIZE: usize = 100 * 1024;
+const SMOKE_SIZE: usize = 1024;
+
+// Loose lower bound to catch regressions like the old O(n^2) `get_char`, which did well under 1 MB/s here
+const MIN_MB_PER_S: f64 = 10.0;
+
+fn identifier_heavy(size: usize) -> String {
+    let mut source: String = String::new();
+    let mut i: usize = 0;
+    while source.len() < size {
+        source += &format!("int some_identifier_{i} = another_identifier_{i};\n");
+        i += 1;
+    }
+    source
+}
+
+fn number_heavy(size: usize) -> String {
+    let mut source: String = String::new();
+    let mut i: usize = 0;
+    while source.len() < size {
+        source += &format!("{i}, {}, 0{:o}, 0, 4294967295,\n", i * 7919, i);
+        i += 1;
+    }
+    source
+}
+
+fn comment_heavy(size: usize) -> String {
+    let mut source: String = String::new();
+    let mut i: usize = 0;
+    while source.len() < size {
+        source += &format!("/* Block comment number {i}\n * spanning a few lines\n */\n// Line comment {i}\nx;\n");
+        i += 1;
+    }
+    source
+}
+
+fn lex_all(source: &str) -> usize {
+    let mut lexer: Lexer = Lexer::new(source, "bench.c".to_string());
+    let mut count: usize = 0;
+    loop {
+        let token: Token = lexer.get_token().unwrap();
+        if token == Token::EOF { return count; }
+        count += 1;
+    }
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`, `cargo test` doesn't
+    let bench: bool = env::args().any(|arg| arg == "--bench");
+    let size: usize = if bench { BENCH_SIZE } else { SMOKE_SIZE };
+
+    let inputs: [(&str, String); 3] = [
+        ("identifier-heavy", identifier_heavy(size)),
+        ("number-heavy", number_heavy(size)),
+        ("comment-heavy", comment_heavy(size)),
+    ];
+
+    for (name, source) in &inputs {
+        let start: Instant = Instant::now();
+        let tokens: usize = lex_all(black_box(source));
+        let elapsed: Duration = start.elapsed();
+
+        let mb_per_s: f64 = source.len() as f64 / 1e6 / elapsed.as_secs_f64();
+        println!("{name:>16}: {:>7} bytes, {tokens:>6} tokens, {mb_per_s:>8.2} MB/s", source.len());
+        if bench {
+            assert!(mb_per_s >= MIN_MB_PER_S, "{name}: {mb_per_s:.2} MB/s is below {MIN_MB_PER_S} MB/s");
+        }
+    }
+}
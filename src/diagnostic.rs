@@ -0,0 +1,164 @@
+use core::fmt;
+use core::fmt::Write;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::lexer::{Lexer, Location};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabRender {
+    Expand, // Replace tabs with spaces up to the next tab stop
+    Keep,   // Reproduce the source tabs in the caret line
+}
+
+/// Render `file:row:col: message` followed by the source line and a `^~~` under the `width`
+/// columns starting at `location`. `location.col` is the visual column, i.e. with tabs expanded to `location.tab_width`.
+///
+/// ```
+/// use mycc::diagnostic::{render, TabRender};
+/// use mycc::lexer::Lexer;
+///
+/// let mut lexer = Lexer::from("\t\tx").tab_width(2);
+/// lexer.get_token().unwrap();
+/// let location = lexer.token_location();
+/// assert_eq!(location.col, 4);
+/// assert_eq!(render("\t\tx", &location, 1, "here", TabRender::Expand), "<anonymous>:1:5: here\n    x\n    ^");
+/// ```
+pub fn render(source: &str, location: &Location, width: usize, message: &str, tab_render: TabRender) -> String {
+    let line_starts: Vec<usize> = Lexer::index_line_starts(source, 0);
+    let line: &str = match line_starts.get(location.row) {
+        Some(&start) => {
+            let end: usize = line_starts.get(location.row + 1).copied().unwrap_or(source.len());
+            source[start..end].trim_end_matches(['\n', '\r'])
+        },
+        None => "",
+    };
+
+    let mut source_line: String = String::new();
+    let mut caret_line: String = String::new();
+    let tab_width: usize = location.tab_width.max(1);
+    let mut col: usize = 0;
+
+    for c in line.chars() {
+        let width: usize = if c == '\t' { tab_width - col % tab_width } else { 1 };
+        let before_caret: bool = col < location.col;
+
+        match (c, tab_render) {
+            ('\t', TabRender::Expand) => {
+                source_line.extend(core::iter::repeat_n(' ', width));
+                if before_caret { caret_line.extend(core::iter::repeat_n(' ', width)); }
+            },
+            ('\t', TabRender::Keep) => {
+                source_line.push('\t');
+                if before_caret { caret_line.push('\t'); }
+            },
+            _ => {
+                source_line.push(c);
+                if before_caret { caret_line.push(' '); }
+            },
+        }
+
+        col += width;
+    }
+    // Spans running past the end of the line (e.g. an unterminated comment) stop at it
+    let width: usize = width.min(col.saturating_sub(location.col)).max(1);
+    caret_line.push('^');
+    caret_line.extend(core::iter::repeat_n('~', width - 1));
+
+    let mut out: String = String::new();
+    let _ = write!(out, "{location}: {message}\n{source_line}\n{caret_line}");
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error   => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub location: Location,
+    pub width: usize, // Columns to underline, e.g. 3 for `<<=`
+    pub severity: Severity,
+    pub message: String,
+    pub code: Option<&'static str>, // e.g. `LexerError::code`
+}
+
+impl Diagnostic {
+    pub fn error(location: Location, message: String) -> Self {
+        Self { location, width: 1, severity: Severity::Error, message, code: None }
+    }
+
+    pub fn warning(location: Location, message: String) -> Self {
+        Self { location, width: 1, severity: Severity::Warning, message, code: None }
+    }
+
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// `error[E0001]: message`, or `error: message` without a code.
+    fn header(&self) -> String {
+        match self.code {
+            Some(code) => format!("{}[{code}]: {}", self.severity, self.message),
+            None       => format!("{}: {}", self.severity, self.message),
+        }
+    }
+
+    /// Render with the offending source line, see `render`.
+    pub fn render(&self, source: &str, tab_render: TabRender) -> String {
+        render(source, &self.location, self.width, &self.header(), tab_render)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}: {}", self.location, self.header())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::string::ToString;
+
+    #[test]
+    fn caret_under_tab_indented_token() {
+        let source: &str = "int x;\n\tfoo = 1;\n";
+        let location: Location = Lexer::from(source).location_at(source.find("foo").unwrap());
+        assert_eq!(location.col, 8);
+
+        let expanded: String = render(source, &location, 3, "here", TabRender::Expand);
+        assert_eq!(expanded, "<anonymous>:2:9: here\n        foo = 1;\n        ^~~");
+
+        let kept: String = render(source, &location, 3, "here", TabRender::Keep);
+        assert_eq!(kept, "<anonymous>:2:9: here\n\tfoo = 1;\n\t^~~");
+    }
+
+    #[test]
+    fn diagnostic_header() {
+        let location: Location = Lexer::from("x").location_at(0);
+        let diagnostic: Diagnostic = Diagnostic::error(location, "bad".to_string()).with_code("E0001");
+        assert_eq!(diagnostic.to_string(), "<anonymous>:1:1: error[E0001]: bad");
+        assert_eq!(diagnostic.render("x", TabRender::Expand), "<anonymous>:1:1: error[E0001]: bad\nx\n^");
+    }
+}
@@ -0,0 +1,271 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::lexer::{BracketMatcher, Lexer, LexerError, Location, Span, Standard, Token};
+use crate::lint;
+
+/// How many tokens to lex between two `Phase::Lexing` progress reports.
+pub const PROGRESS_INTERVAL: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Lexing,
+    Brackets,
+}
+
+/// Passed to the `check_with_progress` callback. `bytes_processed` never decreases within a phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub phase: Phase,
+    pub bytes_processed: usize,
+    pub total_bytes: usize,
+}
+
+/// What the command line can change about `check`.
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    pub standard: Standard,
+    pub warn_implicit_octal: bool, // `-Wimplicit-octal`
+    pub warn_assignment_in_condition: bool, // `-Wparentheses`, see `lint::assignment_in_condition`
+    pub warn_duplicate_specifiers: bool, // `-Wduplicate-decl-specifier`, see `lint::duplicate_specifiers`
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            standard: Standard::C11,
+            warn_implicit_octal: false,
+            warn_assignment_in_condition: false,
+            warn_duplicate_specifiers: false,
+        }
+    }
+}
+
+impl Options {
+    /// Whether any `lint` pass is on, they need every token with its location.
+    fn lints(&self) -> bool {
+        self.warn_assignment_in_condition || self.warn_duplicate_specifiers
+    }
+}
+
+/// Run every stage up to (but not including) codegen and collect the diagnostics.
+/// Returns `Ok` with the warnings if the program compiles, `Err` with all of them (warnings included) if not.
+pub fn check(source: &str, filepath: String) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+    return check_with_progress(source, filepath, Options::default(), |_| {});
+}
+
+/// `check`, calling `progress` at the start and end of every phase
+/// and every `PROGRESS_INTERVAL` tokens while lexing.
+pub fn check_with_progress<F: FnMut(Progress)>(
+    source: &str,
+    filepath: String,
+    options: Options,
+    mut progress: F,
+) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+    // TODO: preprocess, parse and type check once those stages exist
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+    let total_bytes: usize = source.len();
+    let mut report = |phase: Phase, bytes_processed: usize| progress(Progress { phase, bytes_processed, total_bytes });
+
+    let mut lexer: Lexer = Lexer::new(source, filepath)
+        .with_standard(options.standard)
+        .warn_implicit_octal(options.warn_implicit_octal)
+        .emit_newlines(true) // To tell where directive lines end
+        .recovery(true);
+    // Brackets are paired up as they're lexed, so there's only the one pass over the source
+    let mut brackets: BracketMatcher = BracketMatcher::new();
+    let mut tokens: Vec<(Token, Location)> = Vec::new(); // Only kept for the lints
+    // TODO: hand directives to the preprocessor once there is one, until then their lines are skipped
+    let mut directive_rows: Vec<(usize, usize)> = Vec::new(); // First and last row of every directive
+    let mut directive_start: Option<usize> = None;
+    let mut line_start: bool = true;
+    report(Phase::Lexing, 0);
+    let mut token_count: usize = 0;
+    loop {
+        token_count += 1;
+        if token_count.is_multiple_of(PROGRESS_INTERVAL) { report(Phase::Lexing, lexer.token_span().end); }
+
+        let token: Result<Token, LexerError> = lexer.get_token();
+        if let Some(start) = directive_start {
+            if matches!(token, Ok(Token::Newline | Token::EOF)) {
+                directive_rows.push((start, lexer.token_location().row));
+                directive_start = None;
+            } else {
+                continue; // Errors too: `#error don't` isn't an unterminated char constant
+            }
+        }
+        let at_line_start: bool = line_start;
+        line_start = matches!(token, Ok(Token::Newline));
+
+        match token {
+            Ok(Token::EOF) => break,
+            Ok(Token::Newline) => {},
+            Ok(Token::Hash) if at_line_start => directive_start = Some(lexer.token_location().row),
+            // Outside a directive `#` is as unknown as it is without `emit_newlines`
+            Ok(Token::Hash | Token::HashHash) => {
+                let e: LexerError = LexerError::UnknownToken('#');
+                let span: Span = lexer.token_span();
+                diagnostics.push(Diagnostic::error(lexer.token_location(), e.to_string())
+                    .with_width(span.end - span.start)
+                    .with_code(e.code()));
+            },
+            Ok(token) => {
+                brackets.push(&token, || lexer.token_location());
+                if options.lints() { tokens.push((token, lexer.token_location())); }
+            },
+            Err(e) => {
+                let span: Span = lexer.token_span();
+                diagnostics.push(Diagnostic::error(lexer.token_location(), e.to_string())
+                    .with_width(span.end - span.start)
+                    .with_code(e.code()));
+            },
+        }
+    }
+    report(Phase::Lexing, total_bytes);
+    let in_directive = |location: &Location| directive_rows.iter().any(|&(first, last)| (first..=last).contains(&location.row));
+    for (location, e) in lexer.errors() {
+        if in_directive(location) { continue; }
+        diagnostics.push(Diagnostic::error(location.clone(), e.to_string()).with_code(e.code()));
+    }
+    let mut warnings: Vec<Diagnostic> = Vec::new();
+    for (location, warning) in lexer.warnings() {
+        warnings.push(Diagnostic::warning(location.clone(), warning.to_string()).with_code(warning.code()));
+    }
+
+    // Bracket errors are only meaningful on a stream that lexed cleanly
+    if diagnostics.is_empty() {
+        report(Phase::Brackets, 0);
+        for mismatch in brackets.finish() {
+            diagnostics.push(Diagnostic::error(mismatch.location().clone(), mismatch.message()));
+        }
+        report(Phase::Brackets, total_bytes);
+    }
+
+    // So are the lints, with their guesses based on tokens
+    if diagnostics.is_empty() {
+        let mut lints: Vec<Diagnostic> = Vec::new();
+        if options.warn_assignment_in_condition { lints.append(&mut lint::assignment_in_condition(&tokens)); }
+        if options.warn_duplicate_specifiers { lints.append(&mut lint::duplicate_specifiers(&tokens)); }
+        for diagnostic in lints {
+            match diagnostic.severity {
+                Severity::Error => diagnostics.push(diagnostic),
+                _               => warnings.push(diagnostic),
+            }
+        }
+    }
+
+    let failed: bool = !diagnostics.is_empty();
+    diagnostics.append(&mut warnings);
+    diagnostics.sort_by_key(|d| (d.location.row, d.location.col));
+
+    if failed { return Err(diagnostics); }
+    return Ok(diagnostics);
+}
+
+/// `check` every `(filepath, source)` independently, keyed by filepath.
+// TODO: link the results once there is codegen
+pub fn check_files(files: &[(&str, &str)], options: Options) -> BTreeMap<String, Result<Vec<Diagnostic>, Vec<Diagnostic>>> {
+    let mut results: BTreeMap<String, Result<Vec<Diagnostic>, Vec<Diagnostic>>> = BTreeMap::new();
+    for (filepath, source) in files {
+        results.insert(filepath.to_string(), check_with_progress(source, filepath.to_string(), options, |_| {}));
+    }
+    return results;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warnings_are_surfaced() {
+        let options: Options = Options { warn_implicit_octal: true, ..Options::default() };
+        let warnings: Vec<Diagnostic> = check_with_progress("int x = 013;", "a.c".to_string(), options, |_| {}).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].severity, Severity::Warning);
+        assert_eq!(warnings[0].code, Some("W0001"));
+
+        assert!(check("int x = 013;", "a.c".to_string()).unwrap().is_empty());
+        assert_eq!(check("int x = 0b1;", "a.c".to_string()).unwrap()[0].code, Some("W0002"));
+
+        // Warnings come along with the errors when it fails
+        let diagnostics: Vec<Diagnostic> = check_with_progress("int x = 013; #", "a.c".to_string(), options, |_| {}).unwrap_err();
+        assert_eq!(diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn check_reports_diagnostics() {
+        assert_eq!(check("int main() { return 0; }", "a.c".to_string()).unwrap().len(), 0);
+
+        let diagnostics: Vec<Diagnostic> = check("int main() {\n  char c = '';\n  return 0 # 1;\n", "a.c".to_string()).unwrap_err();
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(messages, [
+            "a.c:2:12: error[E0003]: empty character constant",
+            "a.c:3:12: error[E0008]: unknown token `#`",
+        ]);
+
+        let diagnostics: Vec<Diagnostic> = check("int main() {\n  return 0;\n", "a.c".to_string()).unwrap_err();
+        assert_eq!(diagnostics[0].to_string(), "a.c:1:12: error: unmatched '{'");
+    }
+
+    #[test]
+    fn check_files_keeps_files_apart() {
+        let results = check_files(&[("a.c", "int a;"), ("b.c", "int b;\n  @")], Options::default());
+        assert_eq!(results.len(), 2);
+        assert!(results["a.c"].is_ok());
+
+        let diagnostics: &Vec<Diagnostic> = results["b.c"].as_ref().unwrap_err();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location.filepath, "b.c");
+        assert_eq!((diagnostics[0].location.row, diagnostics[0].location.col), (1, 2));
+    }
+
+    #[test]
+    fn progress_is_monotonic() {
+        let source: String = "int x = 1;\n".repeat(PROGRESS_INTERVAL);
+        let mut reports: Vec<Progress> = Vec::new();
+        check_with_progress(&source, "a.c".to_string(), Options::default(), |progress| reports.push(progress)).unwrap();
+
+        let lexing: Vec<usize> = reports.iter().filter(|p| p.phase == Phase::Lexing).map(|p| p.bytes_processed).collect();
+        assert!(lexing.len() > 3, "{lexing:?}");
+        assert!(lexing.windows(2).all(|w| w[0] <= w[1]), "{lexing:?}");
+        assert_eq!(lexing.first(), Some(&0));
+        assert_eq!(lexing.last(), Some(&source.len()));
+
+        assert_eq!(reports.last().unwrap().phase, Phase::Brackets);
+        assert!(reports.iter().all(|p| p.total_bytes == source.len()));
+    }
+
+    #[test]
+    fn directive_lines_are_skipped() {
+        let source: &str = "#include <stdio.h>\n#define OPEN (\n  # error don't \"\\q\n#define TWO \\\n  (1 + 1\nint main() { return 0; }\n#";
+        assert_eq!(check(source, "a.c".to_string()).unwrap().len(), 0);
+
+        let diagnostics: Vec<Diagnostic> = check("int x; # y\nint z ## w;", "a.c".to_string()).unwrap_err();
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(messages, [
+            "a.c:1:8: error[E0008]: unknown token `#`",
+            "a.c:2:7: error[E0008]: unknown token `#`",
+        ]);
+    }
+
+    #[test]
+    fn lints_are_opt_in() {
+        let source: &str = "#define X (a = 1)\nint f(int a) { if (a = 1) return 0; return 1; }";
+        assert!(check(source, "a.c".to_string()).unwrap().is_empty());
+
+        let options: Options = Options { warn_assignment_in_condition: true, ..Options::default() };
+        let warnings: Vec<Diagnostic> = check_with_progress(source, "a.c".to_string(), options, |_| {}).unwrap();
+        assert_eq!(warnings.len(), 1); // Not the one in the directive
+        assert_eq!(warnings[0].location.to_string(), "a.c:2:22");
+
+        // Conflicting specifiers are errors, a repeated qualifier only warns
+        let options: Options = Options { warn_duplicate_specifiers: true, ..Options::default() };
+        let diagnostics: Vec<Diagnostic> = check_with_progress("const const int x;\nsigned unsigned y;", "a.c".to_string(), options, |_| {}).unwrap_err();
+        let severities: Vec<Severity> = diagnostics.iter().map(|d| d.severity).collect();
+        assert_eq!(severities, [Severity::Warning, Severity::Error]);
+        assert!(check_with_progress("const const int x;", "a.c".to_string(), options, |_| {}).is_ok());
+    }
+}
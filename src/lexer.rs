@@ -1,23 +1,167 @@
-use std::fmt;
+use core::fmt;
+use core::hash::{Hash, Hasher};
 
-#[derive(Debug, Clone)]
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LexerError {
     UnterminatedStringLiteral,
+    UnterminatedCharLiteral,
+    EmptyCharLiteral,
+    EmptyExponent, // `1e`
+    UnterminatedComment,
     UnknownEscapeSequence(String),
     UnknownToken(char),
+    InvalidDigit { digit: char, radix: u32 }, // `09`, `0b12`
+    MissingDigits(String),                    // `0x`, `"\x"`
+    IntegerOverflow(String),                  // `18446744073709551616`
+    NumericSuffixError { suffix: String, floating: bool }, // `10LLL`, `3.14fl`
+    EscapeOutOfRange(String),                 // `"\400"`
+    IncompleteUniversalCharacterName(String), // `"\u12"`
+    InvalidUtf8 { byte_offset: usize },
+    MissingBinaryExponent(String),            // `0x1.8`
 }
 
 #[derive(Debug, Clone)]
-pub enum Token<'src> {
+pub enum LexerWarning {
+    ImplicitOctal(String), // `013` is 11, not 13
+    BinaryLiteral(String), // `0b101` before C23
+    InvalidUtf8Replaced { byte_offset: usize },
+    MultiCharCharLiteral(String), // `'ab'`, its value is implementation-defined
+}
+
+impl fmt::Display for LexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            LexerError::UnterminatedStringLiteral  => write!(f, "unterminated string literal"),
+            LexerError::UnterminatedCharLiteral    => write!(f, "unterminated character constant"),
+            LexerError::EmptyCharLiteral           => write!(f, "empty character constant"),
+            LexerError::EmptyExponent              => write!(f, "exponent has no digits"),
+            LexerError::UnterminatedComment        => write!(f, "unterminated comment"),
+            LexerError::UnknownEscapeSequence(seq) => write!(f, "unknown escape sequence `{seq}`"),
+            LexerError::UnknownToken(c)            => write!(f, "unknown token `{c}`"),
+            LexerError::InvalidDigit { digit, radix } => {
+                let base: &str = match radix {
+                    2  => "binary",
+                    8  => "octal",
+                    16 => "hexadecimal",
+                    _  => "decimal",
+                };
+                write!(f, "invalid digit `{digit}` in {base} constant")
+            },
+            LexerError::MissingDigits(prefix)      => write!(f, "no digits after `{prefix}`"),
+            LexerError::IntegerOverflow(text)      => write!(f, "integer constant `{text}` is too large"),
+            LexerError::NumericSuffixError { suffix, floating } => {
+                let constant: &str = if *floating { "floating" } else { "integer" };
+                write!(f, "invalid suffix `{suffix}` on {constant} constant")
+            },
+            LexerError::EscapeOutOfRange(seq)      => write!(f, "escape sequence `{seq}` out of range"),
+            LexerError::IncompleteUniversalCharacterName(seq) => {
+                write!(f, "incomplete universal character name `{seq}`")
+            },
+            LexerError::InvalidUtf8 { byte_offset } => write!(f, "invalid UTF-8 at byte {byte_offset}"),
+            LexerError::MissingBinaryExponent(text) => {
+                write!(f, "hexadecimal floating constant `{text}` requires an exponent")
+            },
+        }
+    }
+}
+
+impl fmt::Display for LexerWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            LexerWarning::ImplicitOctal(text) => write!(f, "`{text}` is an octal constant"),
+            LexerWarning::BinaryLiteral(text) => write!(f, "binary constant `{text}` is a C23 feature"),
+            LexerWarning::InvalidUtf8Replaced { byte_offset } => {
+                write!(f, "invalid UTF-8 at byte {byte_offset} replaced with U+FFFD")
+            },
+            LexerWarning::MultiCharCharLiteral(text) => write!(f, "multi-character character constant `{text}`"),
+        }
+    }
+}
+
+// Diagnostic codes are stable: never renumber or reuse one, new variants get the next free code.
+// E0004 is retired, multi-character constants are valid C and became the W0004 warning.
+
+impl LexerError {
+    /// Stable code for tooling and tests to match on instead of the message.
+    ///
+    /// ```
+    /// use mycc::lexer::LexerError;
+    ///
+    /// let errors = [
+    ///     LexerError::UnterminatedStringLiteral,
+    ///     LexerError::UnterminatedCharLiteral,
+    ///     LexerError::EmptyCharLiteral,
+    ///     LexerError::EmptyExponent,
+    ///     LexerError::UnterminatedComment,
+    ///     LexerError::UnknownEscapeSequence("\\q".to_string()),
+    ///     LexerError::UnknownToken('$'),
+    ///     LexerError::InvalidDigit { digit: '9', radix: 8 },
+    ///     LexerError::MissingDigits("0x".to_string()),
+    ///     LexerError::IntegerOverflow("99999999999999999999".to_string()),
+    ///     LexerError::NumericSuffixError { suffix: "LLL".to_string(), floating: false },
+    ///     LexerError::EscapeOutOfRange("\\400".to_string()),
+    ///     LexerError::IncompleteUniversalCharacterName("\\u12".to_string()),
+    ///     LexerError::InvalidUtf8 { byte_offset: 0 },
+    ///     LexerError::MissingBinaryExponent("0x1.8".to_string()),
+    /// ];
+    /// let codes: Vec<&str> = errors.iter().map(LexerError::code).collect();
+    /// assert_eq!(codes, [
+    ///     "E0001", "E0002", "E0003", "E0005", "E0006", "E0007", "E0008", "E0009",
+    ///     "E0010", "E0011", "E0012", "E0013", "E0014", "E0015", "E0016",
+    /// ]);
+    /// ```
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexerError::UnterminatedStringLiteral           => "E0001",
+            LexerError::UnterminatedCharLiteral             => "E0002",
+            LexerError::EmptyCharLiteral                    => "E0003",
+            LexerError::EmptyExponent                       => "E0005",
+            LexerError::UnterminatedComment                 => "E0006",
+            LexerError::UnknownEscapeSequence(_)            => "E0007",
+            LexerError::UnknownToken(_)                     => "E0008",
+            LexerError::InvalidDigit { .. }                 => "E0009",
+            LexerError::MissingDigits(_)                    => "E0010",
+            LexerError::IntegerOverflow(_)                  => "E0011",
+            LexerError::NumericSuffixError { .. }           => "E0012",
+            LexerError::EscapeOutOfRange(_)                 => "E0013",
+            LexerError::IncompleteUniversalCharacterName(_) => "E0014",
+            LexerError::InvalidUtf8 { .. }                  => "E0015",
+            LexerError::MissingBinaryExponent(_)            => "E0016",
+        }
+    }
+}
+
+impl LexerWarning {
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexerWarning::ImplicitOctal(_)           => "W0001",
+            LexerWarning::BinaryLiteral(_)           => "W0002",
+            LexerWarning::InvalidUtf8Replaced { .. } => "W0003",
+            LexerWarning::MultiCharCharLiteral(_)    => "W0004",
+        }
+    }
+}
+
+/// What kind of token it is, without the payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
     // Special
     EOF,
-    ID(&'src str),
+    Newline,         // only with `emit_newlines`
+    ID,              // foo
+
+    Keyword,         // int, return, _Noreturn
 
     // Literals
-    Int(i32),        // 123
-    Float(f32),      // 45.32f
-    Char(char),      // 'a'
-    String(String),  // "Hello, World!"
+    Number,          // 123, 45.32f, 10ULL
+    Char,            // 'a'
+    String,          // "Hello, World!"
 
     // Operators
     Plus,            // +
@@ -46,303 +190,3016 @@ pub enum Token<'src> {
     MultiplyEqual,   // *=
     DivideEqual,     // /=
     ModEqual,        // %=
+    AndEqual,        // &=
     OrEqual,         // |=
     XorEqual,        // ^=
     ShiftLeftEqual,  // <<=
     ShiftRightEqual, // >>=  `ShREq` operator :)
     Arrow,           // ->
-    
+    Question,        // ?
+    Colon,           // :
+    Dot,             // .
+    Tilde,           // ~
+    Not,             // !
+
     // Separators
     OParen,          // (
     CParen,          // )
     OCurly,          // {
     CCurly,          // }
+    OBracket,        // [
+    CBracket,        // ]
     Comma,           // ,
     SemiColon,       // ;
-}
+    Ellipsis,        // ...  variadic parameters
 
-impl<'src> PartialEq for Token<'src> {
-    fn eq(&self, other: &Self) -> bool {
-        use std::mem;
-        mem::discriminant(self) == mem::discriminant(other)
-    }
+    // Preprocessing, only with `emit_newlines`
+    Hash,            // #
+    HashHash,        // ##
+
+    // Extensions
+    At,              // @
+    Backtick,        // `
+    DotDot,          // ..  ranges, e.g. `1..5`
 }
 
 #[derive(Debug, Clone)]
-pub struct Location {
-    pub filepath: String,
-    pub row: usize,
-    pub col: usize,
-}
+pub enum Token<'src> {
+    // Special
+    EOF,
+    Newline,         // only with `emit_newlines`
+    ID(Cow<'src, str>), // Borrowed unless it was split by a `\` + newline
 
-impl fmt::Display for Location {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        write!(f, "{}:{}:{}", self.filepath, self.row + 1, self.col + 1)
-    }
+    Keyword(Keyword), // int, return, _Noreturn
+
+    // Literals
+    Number {         // 123, 45.32f, 10ULL
+        value: NumValue,
+        ty: NumType,
+        raw: Span,
+    },
+    Char {           // 'a', L'a'
+        value: char,
+        encoding: Encoding,
+    },
+    String {         // "Hello, World!", u8"Hello"
+        value: String,
+        encoding: Encoding,
+    },
+
+    // Operators
+    Plus,            // +
+    Minus,           // -
+    Multiply,        // *
+    Divide,          // /
+    Mod,             // %
+    And,             // &
+    Or,              // |
+    Xor,             // ^
+    ShiftLeft,       // <<
+    ShiftRight,      // >>
+    Equal,           // =
+    EqualEqual,      // ==
+    NotEqual,        // !=
+    Less,            // <
+    LessEqual,       // <=
+    Greater,         // >
+    GreaterEqual,    // >=
+    AndAnd,          // &&
+    OrOr,            // ||
+    PlusPlus,        // ++
+    MinusMinus,      // --
+    PlusEqual,       // +=
+    MinusEqual,      // -=
+    MultiplyEqual,   // *=
+    DivideEqual,     // /=
+    ModEqual,        // %=
+    AndEqual,        // &=
+    OrEqual,         // |=
+    XorEqual,        // ^=
+    ShiftLeftEqual,  // <<=
+    ShiftRightEqual, // >>=  `ShREq` operator :)
+    Arrow,           // ->
+    Question,        // ?
+    Colon,           // :
+    Dot,             // .
+    Tilde,           // ~
+    Not,             // !
+
+    // Separators
+    OParen,          // (
+    CParen,          // )
+    OCurly,          // {
+    CCurly,          // }
+    OBracket,        // [
+    CBracket,        // ]
+    Comma,           // ,
+    SemiColon,       // ;
+    Ellipsis,        // ...  variadic parameters
+
+    // Preprocessing, only with `emit_newlines`
+    Hash,            // #
+    HashHash,        // ##
+
+    // Extensions
+    At,              // @
+    Backtick,        // `
+    DotDot,          // ..  ranges, e.g. `1..5`
 }
 
 #[derive(Debug, Clone)]
-pub struct Lexer<'src> {
-    source: &'src str,
-    filepath: String,
-
-    cur: usize, // Cursor
-    row: usize, // Current row
-    bol: usize, // Start of current row
+pub enum BracketMismatch {
+    Unclosed { open: (char, Location) },                            // unmatched '{'
+    Unopened { close: (char, Location) },                           // ')' has no opener
+    Mismatched { open: (char, Location), close: (char, Location) }, // ')' closes '{'
 }
 
-impl<'src> Lexer<'src> {
-    pub fn new(source: &'src str, filepath: String) -> Self {
-        Self {
-            source,
-            filepath,
-            cur: 0,
-            row: 0,
-            bol: 0,
+impl BracketMismatch {
+    /// Where to point the diagnostic: the offending bracket.
+    pub fn location(&self) -> &Location {
+        match self {
+            BracketMismatch::Unclosed { open: (_, loc) } => loc,
+            BracketMismatch::Unopened { close: (_, loc) } => loc,
+            BracketMismatch::Mismatched { close: (_, loc), .. } => loc,
         }
     }
 
-    pub fn expect_token(&mut self, expected_token: Token) -> Result<Option<Token<'src>>, LexerError> {
-        match self.get_token() {
-            Ok(token) => Ok(if token == expected_token {
-                Some(token)
-            } else {
-                None
-            }),
-            Err(e) => Err(e),
+    pub fn message(&self) -> String {
+        match self {
+            BracketMismatch::Unclosed { open: (c, _) } => format!("unmatched '{c}'"),
+            BracketMismatch::Unopened { close: (c, _) } => format!("'{c}' has no opener"),
+            BracketMismatch::Mismatched { open: (o, open_loc), close: (c, _) } => {
+                format!("'{c}' does not match '{o}' opened at {open_loc}")
+            },
         }
     }
+}
 
-    pub fn get_token(&mut self) -> Result<Token<'src>, LexerError> {
-        self.trim_left();
-        if self.is_empty() { return Ok(Token::EOF); }
+impl fmt::Display for BracketMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}: {}", self.location(), self.message())
+    }
+}
 
-        // TODO: add comments support. Maybe remove those in preprocessor stage???
+/// Pairs up brackets from tokens fed one at a time, for callers that are already lexing
+/// and don't want a second pass. `Lexer::match_brackets` runs one over the rest of a lexer.
+#[derive(Debug, Default)]
+pub struct BracketMatcher {
+    open: Vec<(char, Location)>,
+    mismatches: Vec<BracketMismatch>,
+}
 
-        let first_char = self.get_char().unwrap();
+impl BracketMatcher {
+    pub fn new() -> Self {
+        return Self::default();
+    }
 
-        match first_char {
-            c if c.is_alphabetic() || c == '_' => self.lex_id(),
-            c if c.is_ascii_digit()            => self.lex_number(),
-            '\''                               => self.lex_char(),
-            '"'                                => self.lex_string(),
-            _                                  => self.lex_operator_or_separator(),
-        }
-        
-        /*
-        return Ok(match cur_char {
-            '(' => Token::OParen,
-            ')' => Token::CParen,
-            '{' => Token::OCurly,
-            '}' => Token::CCurly,
-            ';' => Token::SemiColon,
-            ',' => Token::Comma,
-
-            '=' => {
-                if self.is_empty() || self.get_char().unwrap() != '=' {
-                    Token::Equal
-                } else {
-                    Token::EqualEqual
-                }
-            },
-            '+' => {
-                if !self.is_empty() {
-                    match self.get_char().unwrap() {
-                        '+' => Token::PlusPlus,
-                        '=' => Token::PlusEqual,
-                        _   => Token::Plus,
-                    }
-                } else {
-                    Token::Plus
-                }
-            },
-            '-' => {
-                if !self.is_empty() {
-                    match self.get_char().unwrap() {
-                        '-' => Token::MinusMinus,
-                        '=' => Token::MinusEqual,
-                        _   => Token::Minus,
-                    }
-                } else {
-                    Token::Minus
-                }
-            },
-            '*' => {
-                if self.is_empty() || self.get_char().unwrap() != '=' {
-                    Token::Multiply
-                } else {
-                    Token::MultiplyEqual
-                }
-            },
-            '/' => {
-                if self.is_empty() || self.get_char().unwrap() != '=' {
-                    Token::Divide
-                } else {
-                    Token::DivideEqual
+    /// Feed the next token. `location` is only called for brackets,
+    /// finding one is a lookup and a filepath clone.
+    pub fn push<F: FnOnce() -> Location>(&mut self, token: &Token, location: F) {
+        let (bracket, opener): (char, Option<char>) = match token {
+            Token::OParen   => return self.open.push(('(', location())),
+            Token::OCurly   => return self.open.push(('{', location())),
+            Token::OBracket => return self.open.push(('[', location())),
+            Token::CParen   => (')', Some('(')),
+            Token::CCurly   => ('}', Some('{')),
+            Token::CBracket => (']', Some('[')),
+            _               => return,
+        };
+        let close: (char, Location) = (bracket, location());
+
+        match self.open.iter().rposition(|(c, _)| Some(*c) == opener) {
+            Some(i) => {
+                // Everything opened after the matching opener was never closed
+                for unclosed in self.open.drain(i + 1..).rev() {
+                    self.mismatches.push(BracketMismatch::Unclosed { open: unclosed });
                 }
+                self.open.pop();
             },
-            '%' => {
-                if self.is_empty() || self.get_char().unwrap() != '=' {
-                    Token::Mod
-                } else {
-                    Token::ModEqual
-                }
+            None => match self.open.pop() {
+                Some(top) => self.mismatches.push(BracketMismatch::Mismatched { open: top, close }),
+                None      => self.mismatches.push(BracketMismatch::Unopened { close }),
             },
-            _ => return Err(LexerError::UnknownToken(cur_char)),
-    });
-         */
+        }
     }
 
-    pub fn get_location(&self) -> Location {
-        Location { filepath: self.filepath.clone(), row: self.row, col: self.cur - self.bol }
+    /// Every mismatch found, with whatever is still open at the end reported as unclosed.
+    pub fn finish(mut self) -> Vec<BracketMismatch> {
+        for unclosed in self.open.into_iter().rev() {
+            self.mismatches.push(BracketMismatch::Unclosed { open: unclosed });
+        }
+        return self.mismatches;
     }
+}
 
-    fn lex_id(&mut self) -> Result<Token<'src>, LexerError> {
-        let start: usize = self.cur;
-        self.consume_while(|c| c.is_alphanumeric() || c == '_');
-        let text = &self.source[start..self.cur];
-        return Ok(Token::ID(text));
-    }
+/// A token with where it starts and ends, see `Lexer::get_spanned_token`.
+#[derive(Debug, Clone)]
+pub struct Spanned<'src> {
+    pub token: Token<'src>,
+    pub span: Span,
+    pub start: Location,
+    pub end: Location,
+}
 
-    fn lex_number(&mut self) -> Result<Token<'src>, LexerError> {
-        let start: usize = self.cur;
-        self.consume_while(|c| c.is_ascii_digit());
-        // TODO: add support for floats, doubles, hexadecimals, octals, etc.
-        let text = &self.source[start..self.cur];
-        let value: i32 = text.parse().unwrap();
-        return Ok(Token::Int(value));
+/// Group tokens by the line they start on, for diff tools. Lines are 1-based as displayed,
+/// a string or comment spanning several lines belongs to its first one.
+///
+/// ```
+/// use mycc::lexer::{group_tokens_by_line, Lexer, Token};
+///
+/// let mut lexer = Lexer::from("int x;\nx = 1;\nreturn x;");
+/// let mut tokens = Vec::new();
+/// loop {
+///     let spanned = lexer.get_spanned_token().unwrap();
+///     if spanned.token == Token::EOF { break; }
+///     tokens.push(spanned);
+/// }
+/// let rows: Vec<usize> = group_tokens_by_line(tokens).iter().map(|(row, _)| *row).collect();
+/// assert_eq!(rows, [1, 2, 3]);
+/// ```
+pub fn group_tokens_by_line<'src>(tokens: impl IntoIterator<Item = Spanned<'src>>) -> Vec<(usize, Vec<Token<'src>>)> {
+    let mut lines: Vec<(usize, Vec<Token<'src>>)> = Vec::new();
+    for spanned in tokens {
+        let row: usize = spanned.start.row + 1;
+        match lines.last_mut() {
+            Some((last_row, line)) if *last_row == row => line.push(spanned.token),
+            _ => lines.push((row, vec![spanned.token])),
+        }
     }
+    return lines;
+}
 
-    fn lex_char(&mut self) -> Result<Token<'src>, LexerError> {
-        todo!("lex_char")
+/// Source text that carries no meaning for the parser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trivia<'src> {
+    Whitespace(&'src str),
+    LineComment(&'src str),
+    BlockComment(&'src str),
+}
+
+impl<'src> Trivia<'src> {
+    pub fn text(&self) -> &'src str {
+        match self {
+            Trivia::Whitespace(text) | Trivia::LineComment(text) | Trivia::BlockComment(text) => text,
+        }
     }
-    
-    fn lex_string(&mut self) -> Result<Token<'src>, LexerError> {
-        self.chop_char(); // Skip opening `"`
+}
 
-        let mut string_content: Vec<char> = Vec::new();
-        
-        while !self.is_empty() {
-            let ch: char = self.get_char().unwrap();
+/// A token with its surrounding trivia: everything before it goes to `leading`,
+/// the rest of its line goes to `trailing`. Concatenating all of them gives back the source.
+#[derive(Debug, Clone)]
+pub struct TokenWithTrivia<'src> {
+    pub leading: Vec<Trivia<'src>>,
+    pub token: Token<'src>,
+    pub span: Span,
+    pub trailing: Vec<Trivia<'src>>,
+}
 
-            if ch == '"' {
-                self.chop_char(); // Skip closing `"`
-                let string_content: String = string_content.into_iter().collect();
-                return Ok(Token::String(string_content));
-            }
+impl<'src> Token<'src> {
+    /// `=` and every compound assignment (`+=`, `<<=`, ...): the left operand must be an l-value.
+    pub fn is_assignment_operator(&self) -> bool {
+        matches!(
+            self,
+            Token::Equal
+                | Token::PlusEqual
+                | Token::MinusEqual
+                | Token::MultiplyEqual
+                | Token::DivideEqual
+                | Token::ModEqual
+                | Token::AndEqual
+                | Token::OrEqual
+                | Token::XorEqual
+                | Token::ShiftLeftEqual
+                | Token::ShiftRightEqual
+        )
+    }
 
-            if ch == '\\' {
-                self.chop_char(); // Skip `\`
-                if self.is_empty() { return Err(LexerError::UnterminatedStringLiteral); }
+    /// Compare only the variant, ignoring the contained value. `==` compares values.
+    ///
+    /// ```
+    /// use mycc::lexer::Token;
+    ///
+    /// assert_ne!(Token::ID("a".into()), Token::ID("b".into()));
+    /// assert!(Token::ID("a".into()).same_kind(&Token::ID("b".into())));
+    /// assert_eq!(Token::ID("a".into()), Token::ID("a".into()));
+    /// ```
+    pub fn same_kind(&self, other: &Token) -> bool {
+        return self.kind() == other.kind();
+    }
 
-                let real_char = self.lex_escape_sequence()?;
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            Token::EOF            => TokenKind::EOF,
+            Token::Newline        => TokenKind::Newline,
+            Token::ID(_)          => TokenKind::ID,
+            Token::Keyword(_)     => TokenKind::Keyword,
+            Token::Number { .. }  => TokenKind::Number,
+            Token::Char { .. }    => TokenKind::Char,
+            Token::String { .. }  => TokenKind::String,
+            Token::Plus           => TokenKind::Plus,
+            Token::Minus          => TokenKind::Minus,
+            Token::Multiply       => TokenKind::Multiply,
+            Token::Divide         => TokenKind::Divide,
+            Token::Mod            => TokenKind::Mod,
+            Token::And            => TokenKind::And,
+            Token::Or             => TokenKind::Or,
+            Token::Xor            => TokenKind::Xor,
+            Token::ShiftLeft      => TokenKind::ShiftLeft,
+            Token::ShiftRight     => TokenKind::ShiftRight,
+            Token::Equal          => TokenKind::Equal,
+            Token::EqualEqual     => TokenKind::EqualEqual,
+            Token::NotEqual       => TokenKind::NotEqual,
+            Token::Less           => TokenKind::Less,
+            Token::LessEqual      => TokenKind::LessEqual,
+            Token::Greater        => TokenKind::Greater,
+            Token::GreaterEqual   => TokenKind::GreaterEqual,
+            Token::AndAnd         => TokenKind::AndAnd,
+            Token::OrOr           => TokenKind::OrOr,
+            Token::PlusPlus       => TokenKind::PlusPlus,
+            Token::MinusMinus     => TokenKind::MinusMinus,
+            Token::PlusEqual      => TokenKind::PlusEqual,
+            Token::MinusEqual     => TokenKind::MinusEqual,
+            Token::MultiplyEqual  => TokenKind::MultiplyEqual,
+            Token::DivideEqual    => TokenKind::DivideEqual,
+            Token::ModEqual       => TokenKind::ModEqual,
+            Token::AndEqual       => TokenKind::AndEqual,
+            Token::OrEqual        => TokenKind::OrEqual,
+            Token::XorEqual       => TokenKind::XorEqual,
+            Token::ShiftLeftEqual => TokenKind::ShiftLeftEqual,
+            Token::ShiftRightEqual=> TokenKind::ShiftRightEqual,
+            Token::Arrow          => TokenKind::Arrow,
+            Token::Question       => TokenKind::Question,
+            Token::Colon          => TokenKind::Colon,
+            Token::Dot            => TokenKind::Dot,
+            Token::Tilde          => TokenKind::Tilde,
+            Token::Not            => TokenKind::Not,
+            Token::OParen         => TokenKind::OParen,
+            Token::CParen         => TokenKind::CParen,
+            Token::OCurly         => TokenKind::OCurly,
+            Token::CCurly         => TokenKind::CCurly,
+            Token::OBracket       => TokenKind::OBracket,
+            Token::CBracket       => TokenKind::CBracket,
+            Token::Comma          => TokenKind::Comma,
+            Token::SemiColon      => TokenKind::SemiColon,
+            Token::Ellipsis       => TokenKind::Ellipsis,
+            Token::Hash           => TokenKind::Hash,
+            Token::HashHash       => TokenKind::HashHash,
+            Token::At             => TokenKind::At,
+            Token::Backtick       => TokenKind::Backtick,
+            Token::DotDot         => TokenKind::DotDot,
+        }
+    }
+}
 
-                string_content.push(real_char);
+/// Match a token on its variant without spelling out `Token::` for every arm.
+/// Arms take or-patterns and guards like a regular `match`:
+///
+/// ```
+/// use mycc::{match_token, lexer::Token};
+///
+/// let describe = |token: Token| -> String {
+///     match_token!(token,
+///         ID(name) if name.starts_with('_') => format!("reserved {name}"),
+///         ID(name) => format!("id {name}"),
+///         Plus | Minus => "additive".to_string(),
+///         String { value, .. } => format!("string {value:?}"),
+///         _ => "other".to_string(),
+///     )
+/// };
+///
+/// assert_eq!(describe(Token::ID("_x".into())), "reserved _x");
+/// assert_eq!(describe(Token::ID("x".into())), "id x");
+/// assert_eq!(describe(Token::Plus), "additive");
+/// assert_eq!(describe(Token::Minus), "additive");
+/// assert_eq!(describe(Token::String { value: "a".to_string(), encoding: mycc::lexer::Encoding::Plain }), "string \"a\"");
+/// assert_eq!(describe(Token::SemiColon), "other");
+/// ```
+#[macro_export]
+macro_rules! match_token {
+    (@arms $token:expr; [$($arms:tt)*] $(,)?) => {
+        match $token { $($arms)* }
+    };
+    (@arms $token:expr; [$($arms:tt)*] _ $(if $guard:expr)? => $arm:expr $(, $($rest:tt)*)?) => {
+        $crate::match_token!(@arms $token; [$($arms)* _ $(if $guard)? => $arm,] $($($rest)*)?)
+    };
+    (@arms $token:expr; [$($arms:tt)*]
+        $($variant:ident $(($($binding:pat),* $(,)?))? $({ $($field:tt)* })?)|+ $(if $guard:expr)? => $arm:expr
+        $(, $($rest:tt)*)?
+    ) => {
+        $crate::match_token!(@arms $token; [
+            $($arms)* $($crate::lexer::Token::$variant $(($($binding),*))? $({ $($field)* })?)|+ $(if $guard)? => $arm,
+        ] $($($rest)*)?)
+    };
+    ($token:expr, $($rest:tt)*) => {
+        $crate::match_token!(@arms $token; [] $($rest)*)
+    };
+}
 
-                self.chop_char();
-                continue;
-            }
-            
-            string_content.push(ch);
-            self.chop_char();
+// Compares kind and payload; `raw` spans are ignored so equal spellings anywhere compare equal.
+impl<'src> PartialEq for Token<'src> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Token::ID(a), Token::ID(b))         => a == b,
+            (Token::Keyword(a), Token::Keyword(b)) => a == b,
+            (
+                Token::Char { value: a, encoding: a_encoding },
+                Token::Char { value: b, encoding: b_encoding },
+            ) => a == b && a_encoding == b_encoding,
+            (
+                Token::String { value: a, encoding: a_encoding },
+                Token::String { value: b, encoding: b_encoding },
+            ) => a == b && a_encoding == b_encoding,
+            (
+                Token::Number { value: a, ty: a_ty, .. },
+                Token::Number { value: b, ty: b_ty, .. },
+            ) => a == b && a_ty == b_ty,
+            _ => self.kind() == other.kind(),
         }
-    
-        return Err(LexerError::UnterminatedStringLiteral);
     }
+}
 
-    fn lex_escape_sequence(&mut self) -> Result<char, LexerError> {
-        // TODO: add support for \nnn, \xhh…, \uhhhh, \Uhhhhhhhh
-        // https://en.wikipedia.org/wiki/Escape_sequences_in_C#Escape_sequences
-        return Ok(
-            match self.get_char().unwrap() {
-                'a' => 0x07 as char, // Alert (Beep, Bell) - Added in C89
-                'b' => 0x08 as char, // Backspace
-                'e' => 0x1B as char, // Escape character
-                'f' => 0x0C as char, // Formfeed Page Break
-                'v' => 0x0B as char, // Vertical Tab
-                
-                '?' => '?',          // Question mark (used to avoid trigraphs)
-                // https://en.wikipedia.org/wiki/Digraphs_and_trigraphs_(programming)#C
-                
-                'n' => '\n',         // Newline
-                'r' => '\r',         // Carriage Return
-                't' => '\t',         // Horizontal Tab
-                
-                '\'' => '\'',        // '
-                '"' => '"',          // "
-                '\\' => '\\',        // \
-                
-                _ => return Err(
-                    LexerError::UnknownEscapeSequence(format!("\\{}", self.get_char().unwrap()))
-                ),
-            }
-        );
+impl<'src> Eq for Token<'src> {}
+
+impl<'src> Hash for Token<'src> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind().hash(state);
+        match self {
+            Token::ID(text)              => text.hash(state),
+            Token::Keyword(keyword)      => keyword.hash(state),
+            Token::Char { value, encoding } => {
+                value.hash(state);
+                encoding.hash(state);
+            },
+            Token::String { value, encoding } => {
+                value.hash(state);
+                encoding.hash(state);
+            },
+            Token::Number { value, ty, .. } => {
+                value.hash(state);
+                ty.hash(state);
+            },
+            _ => {},
+        }
     }
+}
 
-    fn lex_operator_or_separator(&mut self) -> Result<Token<'src>, LexerError> {
-        let cur_char: char = self.get_char().unwrap();
-        self.chop_char();
-        
-        return Ok(
-            match cur_char {
-                '(' => Token::OParen,
-                ')' => Token::CParen,
-                '{' => Token::OCurly,
-                '}' => Token::CCurly,
-                ';' => Token::SemiColon,
-                ',' => Token::Comma,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Keyword {
+    // C89
+    Auto, Break, Case, Char, Const, Continue, Default, Do, Double, Else, Enum, Extern,
+    Float, For, Goto, If, Int, Long, Register, Return, Short, Signed, Sizeof, Static,
+    Struct, Switch, Typedef, Union, Unsigned, Void, Volatile, While,
 
-                '=' => {
-                    if self.is_empty() { return Ok(Token::Equal); }
+    // C99
+    Inline, Restrict, Bool, Complex, Imaginary,
 
-                    let cur_char: char = self.get_char().unwrap();
-                    if cur_char.is_whitespace() || cur_char.is_alphanumeric() {
-                        return Ok(Token::Equal);
-                    }
+    // C11
+    Alignas, Alignof, Atomic, Generic, Noreturn, StaticAssert, ThreadLocal,
 
-                    if cur_char == '=' { return Ok(Token::EqualEqual); }
-                    
-                    return Err(LexerError::UnknownToken(cur_char))
-                },
-                
-                _   => return Err(LexerError::UnknownToken(cur_char)),
-            }
-        );
-    }
+    // C23
+    Nullptr,
+}
 
-    fn consume_while<P>(&mut self, predicate: P) where P: Fn(char) -> bool {
-        while !self.is_empty() && predicate(self.get_char().unwrap()) {
-            self.chop_char();
-        }
-    }
-        
-    fn is_empty(&self) -> bool {
-        self.cur >= self.source.len()
-    }
+// Spelling, keyword, first standard it's a keyword in
+const KEYWORDS: &[(&str, Keyword, Standard)] = &[
+    ("auto",           Keyword::Auto,         Standard::C89),
+    ("break",          Keyword::Break,        Standard::C89),
+    ("case",           Keyword::Case,         Standard::C89),
+    ("char",           Keyword::Char,         Standard::C89),
+    ("const",          Keyword::Const,        Standard::C89),
+    ("continue",       Keyword::Continue,     Standard::C89),
+    ("default",        Keyword::Default,      Standard::C89),
+    ("do",             Keyword::Do,           Standard::C89),
+    ("double",         Keyword::Double,       Standard::C89),
+    ("else",           Keyword::Else,         Standard::C89),
+    ("enum",           Keyword::Enum,         Standard::C89),
+    ("extern",         Keyword::Extern,       Standard::C89),
+    ("float",          Keyword::Float,        Standard::C89),
+    ("for",            Keyword::For,          Standard::C89),
+    ("goto",           Keyword::Goto,         Standard::C89),
+    ("if",             Keyword::If,           Standard::C89),
+    ("int",            Keyword::Int,          Standard::C89),
+    ("long",           Keyword::Long,         Standard::C89),
+    ("register",       Keyword::Register,     Standard::C89),
+    ("return",         Keyword::Return,       Standard::C89),
+    ("short",          Keyword::Short,        Standard::C89),
+    ("signed",         Keyword::Signed,       Standard::C89),
+    ("sizeof",         Keyword::Sizeof,       Standard::C89),
+    ("static",         Keyword::Static,       Standard::C89),
+    ("struct",         Keyword::Struct,       Standard::C89),
+    ("switch",         Keyword::Switch,       Standard::C89),
+    ("typedef",        Keyword::Typedef,      Standard::C89),
+    ("union",          Keyword::Union,        Standard::C89),
+    ("unsigned",       Keyword::Unsigned,     Standard::C89),
+    ("void",           Keyword::Void,         Standard::C89),
+    ("volatile",       Keyword::Volatile,     Standard::C89),
+    ("while",          Keyword::While,        Standard::C89),
+    ("inline",         Keyword::Inline,       Standard::C99),
+    ("restrict",       Keyword::Restrict,     Standard::C99),
+    ("_Bool",          Keyword::Bool,         Standard::C99),
+    ("_Complex",       Keyword::Complex,      Standard::C99),
+    ("_Imaginary",     Keyword::Imaginary,    Standard::C99),
+    ("_Alignas",       Keyword::Alignas,      Standard::C11),
+    ("_Alignof",       Keyword::Alignof,      Standard::C11),
+    ("_Atomic",        Keyword::Atomic,       Standard::C11), // Both `_Atomic int` and `_Atomic(int)`
+    ("_Generic",       Keyword::Generic,      Standard::C11),
+    ("_Noreturn",      Keyword::Noreturn,     Standard::C11),
+    ("_Static_assert", Keyword::StaticAssert, Standard::C11),
+    ("_Thread_local",  Keyword::ThreadLocal,  Standard::C11),
+    ("nullptr",        Keyword::Nullptr,      Standard::C23),
+];
 
-    fn chop_char(&mut self) {
-        if !self.is_empty() {
-            let c: char = self.get_char().unwrap();
-            self.cur += 1;
-            if c == '\n' {
-                self.bol = self.cur;
-                self.row += 1;
-            }
-        }
+impl Keyword {
+    pub fn spelling(self) -> &'static str {
+        KEYWORDS.iter().find(|(_, keyword, _)| *keyword == self).unwrap().0
     }
+}
 
-    fn trim_left(&mut self) {
-        while !self.is_empty() && self.get_char().unwrap().is_whitespace() {
-            self.chop_char();
-        }
-    }
+/// The keyword `name` lexes to under `standard`, if it is one.
+pub fn keyword(name: &str, standard: Standard) -> Option<Keyword> {
+    KEYWORDS.iter()
+        .find(|(spelling, _, since)| *spelling == name && standard >= *since)
+        .map(|(_, keyword, _)| *keyword)
+}
 
-    fn drop_line(&mut self) {
-        while !self.is_empty() && self.get_char().unwrap() == '\n' { self.chop_char(); }
-        if !self.is_empty() { self.chop_char(); }
-    }
+/// `keyword`, but `IF` and `If` are `if` too. Compares ASCII case-insensitively
+/// instead of lowercasing `name`, so `_BOOL` still finds `_Bool`.
+pub fn keyword_ignore_case(name: &str, standard: Standard) -> Option<Keyword> {
+    KEYWORDS.iter()
+        .find(|(spelling, _, since)| spelling.eq_ignore_ascii_case(name) && standard >= *since)
+        .map(|(_, keyword, _)| *keyword)
+}
 
-    fn get_char(&self) -> Option<char> {
-        self.source.chars().nth(self.cur)
+pub fn is_keyword(name: &str, standard: Standard) -> bool {
+    keyword(name, standard).is_some()
+}
+
+/// What `decode_source` does with bytes that aren't valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidUtf8Policy {
+    Strict, // Fail with `LexerError::InvalidUtf8`
+    Lossy,  // Replace invalid sequences with U+FFFD and warn
+    Latin1, // Decode the whole input as Latin-1 instead
+}
+
+#[derive(Debug, Clone)]
+pub struct DecodedSource<'a> {
+    pub text: Cow<'a, str>,
+    pub warning: Option<LexerWarning>,
+}
+
+/// Turn raw file contents into text the lexer can work on.
+/// Valid UTF-8 is borrowed as is, whatever the policy.
+pub fn decode_source(bytes: &[u8], policy: InvalidUtf8Policy) -> Result<DecodedSource<'_>, LexerError> {
+    let byte_offset: usize = match core::str::from_utf8(bytes) {
+        Ok(text) => return Ok(DecodedSource { text: Cow::Borrowed(text), warning: None }),
+        Err(e) => e.valid_up_to(),
+    };
+
+    return match policy {
+        InvalidUtf8Policy::Strict => Err(LexerError::InvalidUtf8 { byte_offset }),
+        InvalidUtf8Policy::Lossy  => Ok(DecodedSource {
+            text: String::from_utf8_lossy(bytes),
+            warning: Some(LexerWarning::InvalidUtf8Replaced { byte_offset }),
+        }),
+        InvalidUtf8Policy::Latin1 => Ok(DecodedSource {
+            text: Cow::Owned(bytes.iter().map(|&b| b as char).collect()),
+            warning: None,
+        }),
+    };
+}
+
+/// Language standard, decides which words are keywords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Standard {
+    C89,
+    C99,
+    C11,
+    C23,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum NumValue {
+    Int(u64),
+    Float(f64),
+}
+
+// Floats compare bitwise so `NumValue` can be `Eq` and `Hash`.
+impl PartialEq for NumValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (NumValue::Int(a), NumValue::Int(b))     => a == b,
+            (NumValue::Float(a), NumValue::Float(b)) => a.to_bits() == b.to_bits(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for NumValue {}
+
+impl Hash for NumValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            NumValue::Int(value)   => { 0u8.hash(state); value.hash(state); },
+            NumValue::Float(value) => { 1u8.hash(state); value.to_bits().hash(state); },
+        }
+    }
+}
+
+/// Prefix of a string or char literal, decides its element type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Plain, // "abc"   char
+    Wide,  // L"abc"  wchar_t
+    U8,    // u8"abc" char (char8_t in C23)
+    U16,   // u"abc"  char16_t
+    U32,   // U"abc"  char32_t
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NumType {
+    Int,
+    UInt,
+    Long,
+    ULong,
+    LongLong,
+    ULongLong,
+    Float,
+    Double,
+    LongDouble,
+}
+
+impl NumType {
+    /// Deduce the type of an unsuffixed integer literal: the first of
+    /// `int`, `unsigned int`, `long`, `unsigned long` its value fits in.
+    /// Decimal literals never become unsigned.
+    pub fn for_int(value: u64, radix: u32) -> Self {
+        Self::for_suffixed_int(value, radix, "")
+    }
+
+    /// `for_int` for a literal with a (valid) suffix like `u`, `L` or `ULL`: the first type
+    /// at least as long as the suffix asks for that fits the value, only unsigned ones with `u`.
+    /// A decimal literal too big for any signed candidate falls back to the widest unsigned one.
+    ///
+    /// ```
+    /// use mycc::lexer::NumType;
+    ///
+    /// assert_eq!(NumType::for_suffixed_int(10, 10, "u"), NumType::UInt);
+    /// assert_eq!(NumType::for_suffixed_int(5, 10, "L"), NumType::Long);
+    /// assert_eq!(NumType::for_suffixed_int(3, 10, "ULL"), NumType::ULongLong);
+    /// assert_eq!(NumType::for_suffixed_int(0xFF, 16, "u"), NumType::UInt);
+    /// assert_eq!(NumType::for_suffixed_int(u32::MAX as u64, 10, ""), NumType::Long);
+    /// ```
+    pub fn for_suffixed_int(value: u64, radix: u32, suffix: &str) -> Self {
+        let unsigned: bool = suffix.contains(['u', 'U']);
+        let length: usize = suffix.chars().filter(|c| matches!(c, 'l' | 'L')).count();
+
+        // Types with their largest value, `long` is 64 bits (LP64)
+        let candidates: [(NumType, u64); 6] = [
+            (NumType::Int,       i32::MAX as u64),
+            (NumType::UInt,      u32::MAX as u64),
+            (NumType::Long,      i64::MAX as u64),
+            (NumType::ULong,     u64::MAX),
+            (NumType::LongLong,  i64::MAX as u64),
+            (NumType::ULongLong, u64::MAX),
+        ];
+        let fits = candidates.iter()
+            .skip(2 * length) // `l` starts at `long`, `ll` at `long long`
+            .filter(|(ty, _)| {
+                let unsigned_ty: bool = matches!(ty, NumType::UInt | NumType::ULong | NumType::ULongLong);
+                if unsigned { unsigned_ty } else { !unsigned_ty || radix != 10 }
+            })
+            .find(|(_, max)| value <= *max);
+
+        return match fits {
+            Some((ty, _)) => *ty,
+            None if length == 2 => NumType::ULongLong,
+            None => NumType::ULong,
+        };
+    }
+}
+
+/// How `normalize_float_literal` spells the parts that don't affect the value.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FloatStyle {
+    pub uppercase_exponent: bool, // `1E3` instead of `1e3`
+    pub uppercase_suffix: bool,   // `1.0F` instead of `1.0f`
+}
+
+/// Normalize a floating literal spelling without changing its value:
+/// `.5` -> `0.5`, `5.` -> `5.0`, `1E3` -> `1e3`.
+pub fn normalize_float_literal(text: &str) -> String {
+    normalize_float_literal_with(text, FloatStyle::default())
+}
+
+pub fn normalize_float_literal_with(text: &str, style: FloatStyle) -> String {
+    let hex: bool = text.starts_with("0x") || text.starts_with("0X");
+    let (prefix, rest): (&str, &str) = if hex { ("0x", &text[2..]) } else { ("", text) };
+
+    // mantissa [exponent marker, sign, digits] suffix
+    let is_marker = |c: char| if hex { c == 'p' || c == 'P' } else { c == 'e' || c == 'E' };
+    let is_digit = |c: char| if hex { c.is_ascii_hexdigit() } else { c.is_ascii_digit() };
+    let mantissa_end: usize = rest.find(|c: char| !(is_digit(c) || c == '.')).unwrap_or(rest.len());
+    let mantissa: &str = &rest[..mantissa_end];
+
+    let mut exponent_end: usize = mantissa_end;
+    if rest[mantissa_end..].starts_with(is_marker) {
+        exponent_end += 1;
+        if rest[exponent_end..].starts_with(['+', '-']) { exponent_end += 1; }
+        exponent_end += rest[exponent_end..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - exponent_end);
+    }
+    let exponent: &str = &rest[mantissa_end..exponent_end];
+    let suffix: &str = &rest[exponent_end..];
+
+    let mut normalized: String = String::from(prefix);
+    if mantissa.starts_with('.') { normalized.push('0'); }
+    normalized.push_str(mantissa);
+    if mantissa.ends_with('.') { normalized.push('0'); }
+
+    if !exponent.is_empty() {
+        let marker: char = if hex { 'p' } else { 'e' };
+        normalized.push(if style.uppercase_exponent { marker.to_ascii_uppercase() } else { marker });
+        normalized.push_str(&exponent[1..]);
+    }
+
+    if style.uppercase_suffix {
+        normalized.push_str(&suffix.to_ascii_uppercase());
+    } else {
+        normalized.push_str(&suffix.to_ascii_lowercase());
+    }
+
+    debug_assert!(
+        hex || normalized.trim_end_matches(['f', 'F', 'l', 'L']).parse::<f64>().ok()
+            == text.trim_end_matches(['f', 'F', 'l', 'L']).parse::<f64>().ok(),
+        "normalizing `{text}` changed its value",
+    );
+    normalized
+}
+
+/// Byte range `start..end` in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Smallest span covering both, e.g. a binary expression from its operands.
+    pub fn merge(a: Span, b: Span) -> Span {
+        Span { start: a.start.min(b.start), end: a.end.max(b.end) }
+    }
+}
+
+pub(crate) const TAB_WIDTH: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub filepath: String,
+    pub row: usize,
+    pub col: usize,
+    pub tab_width: usize, // What `col` expanded tabs to, so a renderer can line up with it
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}:{}:{}", self.filepath, self.row + 1, self.col + 1)
+    }
+}
+
+/// A `LexerError` with where it happened, see `Lexer::locate`.
+/// Displays as `./hw.c:12:5: unterminated string literal`.
+#[derive(Debug, Clone)]
+pub struct LocatedLexerError {
+    pub location: Location,
+    pub error: LexerError,
+}
+
+impl fmt::Display for LocatedLexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}: {}", self.location, self.error)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Lexer<'src> {
+    source: &'src str,
+    filepath: String,
+
+    cur: usize, // Cursor, a byte offset into `source`
+    token_start: usize, // Start of the last lexed token
+    line_starts: Vec<usize>, // Byte offset of every row's start
+
+    options: LexerOptions,
+    limit: Option<usize>, // Byte offset to report `EOF` at
+    warnings: Vec<(Location, LexerWarning)>,
+    errors: Vec<(Location, LexerError)>,
+
+    peeked: Option<(Result<Token<'src>, LexerError>, LexerPos)>, // Next token and the state after it
+}
+
+/// What the builder methods set, kept apart so `StreamLexer` can copy it into a lexer per token.
+#[derive(Debug, Clone, Copy)]
+struct LexerOptions {
+    standard: Standard,
+
+    gnu_extensions: bool,
+    allow_dollar_in_identifiers: bool,
+    allow_at_and_backtick: bool,
+    allow_ranges: bool,
+    emit_newlines: bool,
+    warn_implicit_octal: bool,
+    case_insensitive_keywords: bool,
+    tab_width: usize,
+    concat_strings: bool,
+
+    recovery: bool,
+}
+
+impl Default for LexerOptions {
+    fn default() -> Self {
+        Self {
+            standard: Standard::C11,
+            gnu_extensions: true,
+            allow_dollar_in_identifiers: false,
+            allow_at_and_backtick: false,
+            allow_ranges: false,
+            emit_newlines: false,
+            warn_implicit_octal: false,
+            case_insensitive_keywords: false,
+            tab_width: TAB_WIDTH,
+            concat_strings: false,
+            recovery: false,
+        }
+    }
+}
+
+/// Enough of the lexer state to come back to a position, see `Lexer::mark`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LexerPos {
+    cur: usize,
+    token_start: usize,
+}
+
+/// Iterator over the tokens of a `Lexer`, see `Lexer::iter`.
+pub struct Tokens<'lexer, 'src> {
+    lexer: &'lexer mut Lexer<'src>,
+    done: bool,
+}
+
+impl<'src> Iterator for Tokens<'_, 'src> {
+    type Item = Result<Token<'src>, LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done { return None; }
+
+        let token: Result<Token<'src>, LexerError> = self.lexer.get_token();
+        self.done = matches!(token, Ok(Token::EOF) | Err(_));
+        return Some(token);
+    }
+}
+
+impl<'lexer, 'src> IntoIterator for &'lexer mut Lexer<'src> {
+    type Item = Result<Token<'src>, LexerError>;
+    type IntoIter = Tokens<'lexer, 'src>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Filepath used by `Lexer::from`
+pub const ANONYMOUS_FILEPATH: &str = "<anonymous>";
+
+impl<'src> From<&'src str> for Lexer<'src> {
+    fn from(source: &'src str) -> Self {
+        Self::new(source, ANONYMOUS_FILEPATH.to_string())
+    }
+}
+
+/// A file read into memory for lexing, see `SourceFile::lexer`. Owning the text here lets
+/// any number of lexers (and their tokens) borrow it, and frees it when it's dropped.
+///
+/// ```
+/// use mycc::lexer::SourceFile;
+///
+/// let path = std::env::temp_dir().join("mycc_source_file.c");
+/// std::fs::write(&path, "int x;").unwrap();
+/// let file = SourceFile::read(path.to_str().unwrap()).unwrap();
+/// assert_eq!(file.lexer().tokenize().unwrap().len(), 4);
+/// std::fs::remove_file(&path).unwrap();
+///
+/// assert!(SourceFile::read("./does/not/exist.c").is_err());
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub filepath: String,
+    pub text: String,
+    pub warning: Option<LexerWarning>, // From decoding, e.g. `InvalidUtf8Replaced`
+}
+
+#[cfg(feature = "std")]
+impl SourceFile {
+    /// Read the file at `filepath`. Invalid UTF-8 is replaced as with `InvalidUtf8Policy::Lossy`.
+    pub fn read(filepath: &str) -> std::io::Result<Self> {
+        let bytes: Vec<u8> = std::fs::read(filepath)?;
+        let decoded: DecodedSource = decode_source(&bytes, InvalidUtf8Policy::Lossy).unwrap();
+        Ok(Self { filepath: filepath.to_string(), text: decoded.text.into_owned(), warning: decoded.warning })
+    }
+
+    /// A lexer over the whole file, with the decoding warning (if any) already in its `warnings`.
+    pub fn lexer(&self) -> Lexer<'_> {
+        let mut lexer: Lexer = Lexer::new(&self.text, self.filepath.clone());
+        if let Some(warning) = &self.warning {
+            let byte_offset: usize = match warning {
+                LexerWarning::InvalidUtf8Replaced { byte_offset } => *byte_offset,
+                _ => 0,
+            };
+            lexer.warnings.push((lexer.location_at(byte_offset), warning.clone()));
+        }
+        lexer
+    }
+}
+
+impl<'src> Lexer<'src> {
+    pub fn new(source: &'src str, filepath: String) -> Self {
+        Self::with_capacity(source, filepath, 0)
+    }
+
+    /// Same as `new`, but preallocates the line index for about `lines` rows.
+    pub fn with_capacity(source: &'src str, filepath: String, lines: usize) -> Self {
+        Self {
+            source,
+            filepath,
+            cur: 0,
+            token_start: 0,
+            line_starts: Self::index_line_starts(source, lines),
+            options: LexerOptions::default(),
+            limit: None,
+            warnings: Vec::new(),
+            errors: Vec::new(),
+            peeked: None,
+        }
+    }
+
+    pub fn with_standard(mut self, standard: Standard) -> Self {
+        self.options.standard = standard;
+        self
+    }
+
+    /// Whether `name` is a keyword under this lexer's standard, i.e. wouldn't lex as `Token::ID`.
+    pub fn is_keyword(&self, name: &str) -> bool {
+        if self.options.case_insensitive_keywords { return keyword_ignore_case(name, self.options.standard).is_some(); }
+        is_keyword(name, self.options.standard)
+    }
+
+    /// Accept GNU extensions such as the `\e` escape. Off means strict standard C.
+    pub fn gnu_extensions(mut self, enabled: bool) -> Self {
+        self.options.gnu_extensions = enabled;
+        self
+    }
+
+    /// Accept `$` in identifiers (GNU extension), otherwise it's an unknown token.
+    pub fn allow_dollar_in_identifiers(mut self, enabled: bool) -> Self {
+        self.options.allow_dollar_in_identifiers = enabled;
+        self
+    }
+
+    /// Lex `@` and `` ` `` as `Token::At`/`Token::Backtick` for C-like DSLs,
+    /// otherwise they are unknown tokens. This also covers module syntax like Objective-C's
+    /// `@import`, which is left for the front end to interpret:
+    ///
+    /// ```
+    /// use mycc::lexer::{Lexer, Token};
+    ///
+    /// let tokens = Lexer::from("@import foo;").allow_at_and_backtick(true).tokenize().unwrap();
+    /// assert_eq!(tokens, [Token::At, Token::ID("import".into()), Token::ID("foo".into()), Token::SemiColon, Token::EOF]);
+    /// assert!(Lexer::from("@import foo;").tokenize().is_err());
+    /// ```
+    pub fn allow_at_and_backtick(mut self, enabled: bool) -> Self {
+        self.options.allow_at_and_backtick = enabled;
+        self
+    }
+
+    /// Lex `..` as `Token::DotDot` for dialects with ranges, so `1..5` is `1`, `..`, `5`.
+    /// `...` is always `Token::Ellipsis`, longest first: `1...5` is `1`, `...`, `5`.
+    /// Without ranges a `.` after digits is a fraction as in C: `1..5` is `1.`, `.5`.
+    ///
+    /// ```
+    /// use mycc::lexer::{Lexer, Token};
+    ///
+    /// let kinds = |source: &str, ranges: bool| -> Vec<String> {
+    ///     let tokens = Lexer::from(source).allow_ranges(ranges).tokenize().unwrap();
+    ///     tokens.iter().map(|t| format!("{:?}", t.kind())).collect()
+    /// };
+    /// assert_eq!(kinds("1..5", true), ["Number", "DotDot", "Number", "EOF"]);
+    /// assert_eq!(kinds("1...5", true), ["Number", "Ellipsis", "Number", "EOF"]);
+    /// assert_eq!(kinds("f(int, ...)", true), ["ID", "OParen", "Keyword", "Comma", "Ellipsis", "CParen", "EOF"]);
+    /// assert_eq!(kinds("1..5", false), ["Number", "Number", "EOF"]);
+    /// ```
+    pub fn allow_ranges(mut self, enabled: bool) -> Self {
+        self.options.allow_ranges = enabled;
+        self
+    }
+
+    /// Return `Token::Newline` at the end of every line, so the preprocessor can tell where
+    /// a directive ends. Newlines inside block comments don't count.
+    /// `#` and `##` lex as `Token::Hash`/`Token::HashHash` in this mode, otherwise they are unknown tokens.
+    ///
+    /// ```
+    /// use mycc::lexer::{Lexer, Token};
+    ///
+    /// let tokens = Lexer::from("#define X 1 // c\nX").emit_newlines(true).tokenize().unwrap();
+    /// assert_eq!(tokens[0], Token::Hash);
+    /// assert_eq!(tokens[4], Token::Newline);
+    /// assert_eq!(tokens[5], Token::ID("X".into()));
+    /// ```
+    pub fn emit_newlines(mut self, enabled: bool) -> Self {
+        self.options.emit_newlines = enabled;
+        self
+    }
+
+    /// Warn when a literal with a leading zero (e.g. `013`) is lexed as octal.
+    pub fn warn_implicit_octal(mut self, enabled: bool) -> Self {
+        self.options.warn_implicit_octal = enabled;
+        self
+    }
+
+    /// Match keywords regardless of case for dialects where `IF` is `if`.
+    /// Identifiers keep their spelling.
+    pub fn case_insensitive_keywords(mut self, enabled: bool) -> Self {
+        self.options.case_insensitive_keywords = enabled;
+        self
+    }
+
+    /// Columns a tab advances to the next multiple of in reported locations, 8 by default.
+    /// `1` counts a tab as a single column.
+    pub fn tab_width(mut self, width: usize) -> Self {
+        self.options.tab_width = width.max(1);
+        self
+    }
+
+    /// Merge adjacent string literals into one token like translation phase 6,
+    /// with any whitespace or comments between them: `"a" /* x */ "b"` is `"ab"`.
+    ///
+    /// ```
+    /// use mycc::lexer::{Encoding, Lexer, Token};
+    ///
+    /// let ab = |value: &str| Token::String { value: value.to_string(), encoding: Encoding::Plain };
+    /// let tokens = Lexer::from("\"a\" /*x*/ \"b\"\n\"c\" , \"d\"").concat_strings(true).tokenize().unwrap();
+    /// assert_eq!(tokens, [ab("abc"), Token::Comma, ab("d"), Token::EOF]);
+    /// ```
+    pub fn concat_strings(mut self, enabled: bool) -> Self {
+        self.options.concat_strings = enabled;
+        self
+    }
+
+    pub fn warnings(&self) -> &[(Location, LexerWarning)] {
+        &self.warnings
+    }
+
+    /// Keep lexing past recoverable errors (e.g. bad escapes in a string are replaced by U+FFFD,
+    /// an unterminated string ends at the end of its line),
+    /// collecting them in `errors` instead of returning the first one.
+    pub fn recovery(mut self, enabled: bool) -> Self {
+        self.options.recovery = enabled;
+        self
+    }
+
+    pub fn errors(&self) -> &[(Location, LexerError)] {
+        &self.errors
+    }
+
+    pub fn expect_token(&mut self, expected_kind: TokenKind) -> Result<Option<Token<'src>>, LexerError> {
+        match self.get_token() {
+            Ok(token) => Ok(if token.kind() == expected_kind {
+                Some(token)
+            } else {
+                None
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The rest of the tokens as an iterator. It ends after yielding `Token::EOF` or the first error.
+    ///
+    /// ```
+    /// use mycc::lexer::{Lexer, Token};
+    ///
+    /// let mut lexer = Lexer::from("x = y; z");
+    /// let statement: Vec<_> = lexer.iter().take_while(|t| t != &Ok(Token::SemiColon)).collect();
+    /// assert_eq!(statement, [Ok(Token::ID("x".into())), Ok(Token::Equal), Ok(Token::ID("y".into()))]);
+    ///
+    /// let rest: Vec<_> = lexer.iter().collect();
+    /// assert_eq!(rest, [Ok(Token::ID("z".into())), Ok(Token::EOF)]);
+    /// ```
+    pub fn iter(&mut self) -> Tokens<'_, 'src> {
+        Tokens { lexer: self, done: false }
+    }
+
+    /// Lex the rest of the source, stopping at the first error.
+    /// The last element is always `Token::EOF`.
+    ///
+    /// ```
+    /// use mycc::lexer::{Keyword, Lexer, NumType, NumValue, Span, Token};
+    ///
+    /// let tokens = Lexer::from("int x = 5;").tokenize().unwrap();
+    /// assert_eq!(tokens, [
+    ///     Token::Keyword(Keyword::Int),
+    ///     Token::ID("x".into()),
+    ///     Token::Equal,
+    ///     Token::Number { value: NumValue::Int(5), ty: NumType::Int, raw: Span { start: 8, end: 9 } },
+    ///     Token::SemiColon,
+    ///     Token::EOF,
+    /// ]);
+    /// ```
+    pub fn tokenize(&mut self) -> Result<Vec<Token<'src>>, LexerError> {
+        return self.iter().collect();
+    }
+
+    /// Lex the rest of the source, keeping whitespace and comments.
+    /// The last element is always `Token::EOF`.
+    pub fn tokenize_with_trivia(&mut self) -> Result<Vec<TokenWithTrivia<'src>>, LexerError> {
+        let mut tokens: Vec<TokenWithTrivia<'src>> = Vec::new();
+
+        loop {
+            let leading: Vec<Trivia<'src>> = self.lex_trivia(false)?;
+            let start: usize = self.cur;
+            let token: Token<'src> = self.get_token()?;
+            let span: Span = Span { start, end: self.cur };
+
+            if token == Token::EOF {
+                tokens.push(TokenWithTrivia { leading, token, span, trailing: Vec::new() });
+                return Ok(tokens);
+            }
+
+            let trailing: Vec<Trivia<'src>> = self.lex_trivia(true)?;
+            tokens.push(TokenWithTrivia { leading, token, span, trailing });
+        }
+    }
+
+    /// Lex the rest of the source, reporting brackets that are unclosed, unopened or closed by the wrong kind.
+    pub fn match_brackets(&mut self) -> Result<Vec<BracketMismatch>, LexerError> {
+        let mut matcher: BracketMatcher = BracketMatcher::new();
+        loop {
+            let token: Token<'src> = self.get_token()?;
+            if token == Token::EOF { break; }
+            matcher.push(&token, || self.token_location());
+        }
+        return Ok(matcher.finish());
+    }
+
+    /// Consume tokens up to and including the first one matching `predicate` outside of
+    /// any nested `{ }`, or up to EOF. Meant for panic-mode error recovery.
+    pub fn skip_to<P>(&mut self, predicate: P) -> Result<(), LexerError> where P: Fn(&Token<'src>) -> bool {
+        let mut depth: usize = 0;
+        loop {
+            let token: Token<'src> = self.get_token()?;
+            if token == Token::EOF { return Ok(()); }
+            if depth == 0 && predicate(&token) { return Ok(()); }
+
+            match token {
+                Token::OCurly => depth += 1,
+                Token::CCurly => depth = depth.saturating_sub(1),
+                _ => {},
+            }
+        }
+    }
+
+    /// `get_token`, plus where the token is in the source.
+    pub fn get_spanned_token(&mut self) -> Result<Spanned<'src>, LexerError> {
+        let token: Token<'src> = self.get_token()?;
+        let span: Span = self.token_span();
+        Ok(Spanned { token, span, start: self.location_at(span.start), end: self.location_at(span.end) })
+    }
+
+    /// Return the next token without consuming it, the following `get_token` returns the same one.
+    /// Peeking again before that is allowed and returns the same token again.
+    pub fn peek_token(&mut self) -> Result<Token<'src>, LexerError> {
+        if let Some((token, _)) = &self.peeked { return token.clone(); }
+
+        let before: LexerPos = self.checkpoint();
+        let token: Result<Token<'src>, LexerError> = self.get_token();
+        let after: LexerPos = self.checkpoint();
+        self.restore(before);
+
+        self.peeked = Some((token.clone(), after));
+        return token;
+    }
+
+    pub fn get_token(&mut self) -> Result<Token<'src>, LexerError> {
+        if let Some((token, after)) = self.peeked.take() {
+            self.restore(after);
+            return token;
+        }
+
+        return match self.lex_token() {
+            Ok(Token::String { value, encoding }) if self.options.concat_strings => self.concat_adjacent_strings(value, encoding),
+            token => token,
+        };
+    }
+
+    /// Append the strings following the one just lexed, `"a" /* x */ "b"` is `"ab"`.
+    /// Prefixes must agree, except that a plain string takes the other one's: `"a" L"b"` is `L"ab"`.
+    fn concat_adjacent_strings(&mut self, mut value: String, mut encoding: Encoding) -> Result<Token<'src>, LexerError> {
+        let start: usize = self.token_start;
+        loop {
+            let before: LexerPos = self.checkpoint();
+            let (warnings, errors): (usize, usize) = (self.warnings.len(), self.errors.len());
+
+            match self.lex_token() {
+                Ok(Token::String { value: next, encoding: next_encoding })
+                    if encoding == next_encoding || encoding == Encoding::Plain || next_encoding == Encoding::Plain =>
+                {
+                    value.push_str(&next);
+                    if encoding == Encoding::Plain { encoding = next_encoding; }
+                },
+                // Not a string: leave it (and anything it reported) for the next `get_token`
+                _ => {
+                    self.restore(before);
+                    self.warnings.truncate(warnings);
+                    self.errors.truncate(errors);
+                    break;
+                },
+            }
+        }
+
+        self.token_start = start;
+        return Ok(Token::String { value, encoding });
+    }
+
+    fn lex_token(&mut self) -> Result<Token<'src>, LexerError> {
+        self.skip_trivia()?;
+        self.cur = self.skip_splices(self.cur);
+        self.token_start = self.cur;
+        if self.is_empty() || self.limit.is_some_and(|limit| self.cur >= limit) { return Ok(Token::EOF); }
+
+        let first_char = self.get_char().unwrap();
+
+        match first_char {
+            c if self.is_id_start(c)           => self.lex_id(),
+            c if c.is_ascii_digit()            => self.lex_number(),
+            '.' if self.peek_char(1).is_some_and(|c| c.is_ascii_digit()) => self.lex_number(), // `.5`
+            '\''                               => self.lex_char(Encoding::Plain),
+            '"'                                => self.lex_string(Encoding::Plain),
+            _                                  => self.lex_operator_or_separator(),
+        }
+    }
+
+    /// Location of the cursor, counted the same way as `location_at`.
+    pub fn get_location(&self) -> Location {
+        self.location_at(self.cur)
+    }
+
+    /// Return `EOF` for tokens starting at or after `byte_offset`, e.g. to only lex up to
+    /// the cursor in an editor. A token starting before it is returned whole.
+    ///
+    /// ```
+    /// use mycc::lexer::{Keyword, Lexer, Token};
+    ///
+    /// let mut lexer = Lexer::from("int x = 5;");
+    /// lexer.set_limit(5);
+    /// assert_eq!(lexer.tokenize().unwrap(), [Token::Keyword(Keyword::Int), Token::ID("x".into()), Token::EOF]);
+    /// ```
+    pub fn set_limit(&mut self, byte_offset: usize) {
+        self.limit = Some(byte_offset);
+        self.peeked = None; // It may be past the new limit, peeking doesn't move the cursor
+    }
+
+    /// Attach the location of the token that failed to an error `get_token` just returned.
+    ///
+    /// ```
+    /// use mycc::lexer::Lexer;
+    ///
+    /// let mut lexer = Lexer::new("int x;\n  char *s = \"abc", "./hw.c".to_string());
+    /// let e = lexer.tokenize().unwrap_err();
+    /// assert_eq!(lexer.locate(e).to_string(), "./hw.c:2:13: unterminated string literal");
+    ///
+    /// let mut lexer = Lexer::new("a # b", "./hw.c".to_string());
+    /// let e = lexer.tokenize().unwrap_err();
+    /// assert_eq!(lexer.locate(e).to_string(), "./hw.c:1:3: unknown token `#`");
+    /// ```
+    pub fn locate(&self, error: LexerError) -> LocatedLexerError {
+        LocatedLexerError { location: self.token_location(), error }
+    }
+
+    /// Location of the last token (or error) returned by `get_token`.
+    pub fn token_location(&self) -> Location {
+        self.location_at(self.token_start)
+    }
+
+    /// Bytes of the last token returned by `get_token`, e.g. all three of `<<=`.
+    pub fn token_span(&self) -> Span {
+        Span { start: self.token_start, end: self.cur }
+    }
+
+    /// Map a byte offset in the source back to its row and column, in chars with tabs
+    /// expanded to the next multiple of `tab_width`.
+    pub fn location_at(&self, byte_offset: usize) -> Location {
+        let offset: usize = byte_offset.min(self.source.len());
+        let row: usize = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let bol: usize = self.line_starts[row];
+
+        let mut col: usize = 0;
+        for &b in &self.source.as_bytes()[bol..offset] {
+            if b == b'\t' {
+                col += self.options.tab_width - col % self.options.tab_width;
+            } else if b & 0xC0 != 0x80 { // Skip UTF-8 continuation bytes
+                col += 1;
+            }
+        }
+
+        Location { filepath: self.filepath.clone(), row, col, tab_width: self.options.tab_width }
+    }
+
+    /// Byte offset of every line's start. `\n`, `\r\n` and a lone `\r` each end a line.
+    pub(crate) fn index_line_starts(source: &str, lines: usize) -> Vec<usize> {
+        let bytes: &[u8] = source.as_bytes();
+        let mut line_starts: Vec<usize> = Vec::with_capacity(lines.max(1));
+        line_starts.push(0);
+        for (i, &b) in bytes.iter().enumerate() {
+            let line_end: bool = b == b'\n' || (b == b'\r' && bytes.get(i + 1) != Some(&b'\n'));
+            if line_end { line_starts.push(i + 1); }
+        }
+        line_starts
+    }
+
+    fn is_id_start(&self, c: char) -> bool {
+        c.is_alphabetic() || c == '_' || (self.options.allow_dollar_in_identifiers && c == '$')
+    }
+
+    fn lex_id(&mut self) -> Result<Token<'src>, LexerError> {
+        let start: usize = self.cur;
+        let dollar: bool = self.options.allow_dollar_in_identifiers;
+        self.consume_while(|c| c.is_alphanumeric() || c == '_' || (dollar && c == '$'));
+        let text: Cow<'src, str> = self.spliced_text(start, self.cur);
+
+        // A prefix only counts right before the quote: `L"abc"`, but `u8x` is an identifier
+        let encoding: Option<Encoding> = match &*text {
+            "L"  => Some(Encoding::Wide),
+            "u8" => Some(Encoding::U8),
+            "u"  => Some(Encoding::U16),
+            "U"  => Some(Encoding::U32),
+            _    => None,
+        };
+        if let Some(encoding) = encoding {
+            match self.get_char() {
+                Some('"') => return self.lex_string(encoding),
+                Some('\'') if encoding != Encoding::U8 || self.options.standard >= Standard::C23 => return self.lex_char(encoding),
+                _ => {},
+            }
+        }
+
+        let keyword: Option<Keyword> = if self.options.case_insensitive_keywords {
+            keyword_ignore_case(&text, self.options.standard)
+        } else {
+            keyword(&text, self.options.standard)
+        };
+        if let Some(keyword) = keyword { return Ok(Token::Keyword(keyword)); }
+        return Ok(Token::ID(text));
+    }
+
+    fn lex_number(&mut self) -> Result<Token<'src>, LexerError> {
+        let start: usize = self.cur;
+
+        if self.starts_with("0x") || self.starts_with("0X") { return self.lex_prefixed_integer(16); }
+        if self.starts_with("0b") || self.starts_with("0B") { return self.lex_prefixed_integer(2); }
+
+        self.consume_while(|c| c.is_ascii_digit());
+
+        // With ranges a `.` only belongs to the number if the next char isn't another `.`: `1..5` is a range.
+        let fraction: bool = self.get_char() == Some('.') && !(self.options.allow_ranges && self.starts_with(".."));
+        if fraction {
+            self.chop_char();
+            self.consume_while(|c| c.is_ascii_digit());
+        }
+
+        let exponent: bool = matches!(self.get_char(), Some('e' | 'E'));
+        if exponent {
+            self.chop_char();
+            if matches!(self.get_char(), Some('+' | '-')) { self.chop_char(); }
+
+            let digits_start: usize = self.cur;
+            self.consume_while(|c| c.is_ascii_digit());
+            if self.cur == digits_start { return Err(LexerError::EmptyExponent); }
+        }
+
+        if fraction || exponent {
+            let value: f64 = self.spliced_text(start, self.cur).parse().unwrap();
+            let ty: NumType = match &*self.lex_numeric_suffix(true)? {
+                "f" | "F" => NumType::Float,
+                "l" | "L" => NumType::LongDouble,
+                _ => NumType::Double,
+            };
+
+            let raw: Span = Span { start, end: self.cur };
+            return Ok(Token::Number { value: NumValue::Float(value), ty, raw });
+        }
+
+        let text: Cow<str> = self.spliced_text(start, self.cur);
+        let suffix: Cow<str> = self.lex_numeric_suffix(false)?;
+        let raw: Span = Span { start, end: self.cur };
+
+        // Leading zero means octal: `013` == 11
+        let (value, radix): (Result<u64, _>, u32) = if text.len() > 1 && text.starts_with('0') {
+            if let Some(digit) = text.chars().find(|c| *c > '7') {
+                return Err(LexerError::InvalidDigit { digit, radix: 8 });
+            }
+            if self.options.warn_implicit_octal {
+                self.warnings.push((self.location_at(start), LexerWarning::ImplicitOctal(text.to_string())));
+            }
+            (u64::from_str_radix(&text[1..], 8), 8)
+        } else {
+            (text.parse(), 10)
+        };
+        let value: u64 = value.map_err(|_| LexerError::IntegerOverflow(text.to_string()))?;
+
+        let ty: NumType = NumType::for_suffixed_int(value, radix, &suffix);
+        return Ok(Token::Number { value: NumValue::Int(value), ty, raw });
+    }
+
+    /// `0x1F` or `0b1010`, or a hex float like `0x1.8p-2`
+    fn lex_prefixed_integer(&mut self, radix: u32) -> Result<Token<'src>, LexerError> {
+        let start: usize = self.cur;
+        self.chop_char();
+        self.chop_char(); // Skip `0x`/`0b`
+
+        // Take all decimal digits even in binary so `0b12` is an error, not `0b1` followed by `2`
+        let digits_start: usize = self.cur;
+        self.consume_while(|c| c.is_ascii_digit() || (radix == 16 && c.is_ascii_hexdigit()));
+        if radix == 16 && matches!(self.get_char(), Some('.' | 'p' | 'P')) {
+            return self.lex_hex_float(start, digits_start);
+        }
+        let digits: Cow<str> = self.spliced_text(digits_start, self.cur);
+
+        if digits.is_empty() { return Err(LexerError::MissingDigits(self.spliced_text(start, digits_start).into_owned())); }
+        if let Some(digit) = digits.chars().find(|c| !c.is_digit(radix)) {
+            return Err(LexerError::InvalidDigit { digit, radix });
+        }
+
+        if radix == 2 && self.options.standard < Standard::C23 {
+            let text: String = self.spliced_text(start, self.cur).into_owned();
+            self.warnings.push((self.location_at(start), LexerWarning::BinaryLiteral(text)));
+        }
+
+        let value: u64 = u64::from_str_radix(&digits, radix)
+            .map_err(|_| LexerError::IntegerOverflow(self.spliced_text(start, self.cur).into_owned()))?;
+        let suffix: Cow<str> = self.lex_numeric_suffix(false)?;
+        let raw: Span = Span { start, end: self.cur };
+        let ty: NumType = NumType::for_suffixed_int(value, radix, &suffix);
+        return Ok(Token::Number { value: NumValue::Int(value), ty, raw });
+    }
+
+    /// The rest of a hex float after its integer digits: `.8p-2` of `0x1.8p-2`.
+    /// The binary exponent is required, its digits are decimal and it scales by powers of 2.
+    fn lex_hex_float(&mut self, start: usize, digits_start: usize) -> Result<Token<'src>, LexerError> {
+        if self.chop_if('.') { self.consume_while(|c| c.is_ascii_hexdigit()); }
+        let mantissa: String = self.spliced_text(digits_start, self.cur).into_owned();
+        // `0x.p1` and `0xp1` have no digits at all
+        if !mantissa.contains(|c: char| c.is_ascii_hexdigit()) {
+            return Err(LexerError::MissingDigits(self.spliced_text(start, digits_start).into_owned()));
+        }
+
+        if !matches!(self.get_char(), Some('p' | 'P')) {
+            return Err(LexerError::MissingBinaryExponent(self.spliced_text(start, self.cur).into_owned()));
+        }
+        self.chop_char();
+        let exponent_start: usize = self.cur;
+        if matches!(self.get_char(), Some('+' | '-')) { self.chop_char(); }
+        let exponent_digits: usize = self.cur;
+        self.consume_while(|c| c.is_ascii_digit());
+        if self.cur == exponent_digits { return Err(LexerError::EmptyExponent); }
+
+        let exponent_text: Cow<str> = self.spliced_text(exponent_start, self.cur);
+        let negative: bool = exponent_text.starts_with('-');
+        let exponent: i64 = exponent_text.parse().unwrap_or(if negative { i64::MIN } else { i64::MAX });
+
+        // Each hex digit after the `.` is another 4 bits of fraction
+        let fraction_digits: usize = mantissa.split_once('.').map_or(0, |(_, fraction)| fraction.len());
+        let mut value: f64 = mantissa.chars().filter_map(|c| c.to_digit(16)).fold(0.0, |value, d| value * 16.0 + d as f64);
+        // Scale one power of 2 at a time, `powi` isn't in `core`. Past ±2200 it's `inf` or `0` anyway.
+        let scale: i64 = exponent.saturating_sub(4 * fraction_digits as i64).clamp(-2200, 2200);
+        for _ in 0..scale.unsigned_abs() {
+            value = if scale > 0 { value * 2.0 } else { value / 2.0 };
+        }
+
+        let ty: NumType = match &*self.lex_numeric_suffix(true)? {
+            "f" | "F" => NumType::Float,
+            "l" | "L" => NumType::LongDouble,
+            _ => NumType::Double,
+        };
+        let raw: Span = Span { start, end: self.cur };
+        return Ok(Token::Number { value: NumValue::Float(value), ty, raw });
+    }
+
+    /// Whatever identifier chars follow a number, which must be a valid suffix: `10ul`, `1.5f`.
+    fn lex_numeric_suffix(&mut self, floating: bool) -> Result<Cow<'src, str>, LexerError> {
+        let start: usize = self.cur;
+        self.consume_while(|c| c.is_alphanumeric() || c == '_');
+        let suffix: Cow<'src, str> = self.spliced_text(start, self.cur);
+
+        let text: &str = &suffix;
+        let valid: bool = if floating {
+            matches!(text, "" | "f" | "F" | "l" | "L")
+        } else {
+            // `u` on either side of `l`, `ll` (same case)
+            let length: &str = text.strip_prefix(['u', 'U']).or_else(|| text.strip_suffix(['u', 'U'])).unwrap_or(text);
+            matches!(length, "" | "l" | "L" | "ll" | "LL")
+        };
+        if !valid { return Err(LexerError::NumericSuffixError { suffix: suffix.to_string(), floating }); }
+        return Ok(suffix);
+    }
+
+    fn lex_char(&mut self, encoding: Encoding) -> Result<Token<'src>, LexerError> {
+        self.chop_char(); // Skip opening `'`
+        if self.chop_if('\'') { return Err(LexerError::EmptyCharLiteral); }
+
+        let mut value: char = '\0';
+        let mut count: usize = 0;
+        while !self.chop_if('\'') {
+            value = match self.get_char() {
+                None | Some('\n' | '\r') => return Err(LexerError::UnterminatedCharLiteral),
+                Some('\\') => {
+                    let escape_start: usize = self.cur;
+                    self.chop_char(); // Skip `\`
+                    if self.is_empty() { return Err(LexerError::UnterminatedCharLiteral); }
+
+                    self.lex_escape_or_recover(escape_start)?
+                },
+                Some(ch) => {
+                    self.chop_char();
+                    ch
+                },
+            };
+            count += 1;
+        }
+
+        // `'ab'` is valid C but its value is implementation-defined and doesn't fit
+        // a `char`, so warn and keep the last one like GCC does for wide constants
+        if count > 1 {
+            let text: String = self.spliced_text(self.token_start, self.cur).into_owned();
+            self.warnings.push((self.location_at(self.token_start), LexerWarning::MultiCharCharLiteral(text)));
+        }
+        return Ok(Token::Char { value, encoding });
+    }
+
+    fn lex_string(&mut self, encoding: Encoding) -> Result<Token<'src>, LexerError> {
+        self.chop_char(); // Skip opening `"`
+
+        let mut string_content: Vec<char> = Vec::new();
+        
+        while !self.is_empty() {
+            let ch: char = self.get_char().unwrap();
+
+            if ch == '"' {
+                self.chop_char(); // Skip closing `"`
+                let string_content: String = string_content.into_iter().collect();
+                return Ok(Token::String { value: string_content, encoding });
+            }
+
+            // A string never spans lines, so resume on the next one instead of eating the file
+            if matches!(ch, '\n' | '\r') && self.options.recovery {
+                self.errors.push((self.location_at(self.token_start), LexerError::UnterminatedStringLiteral));
+                let string_content: String = string_content.into_iter().collect();
+                return Ok(Token::String { value: string_content, encoding });
+            }
+
+            if ch == '\\' {
+                let escape_start: usize = self.cur;
+                self.chop_char(); // Skip `\`
+                if self.is_empty() { return Err(LexerError::UnterminatedStringLiteral); }
+
+                let real_char = self.lex_escape_or_recover(escape_start)?;
+
+                string_content.push(real_char);
+                continue;
+            }
+            
+            string_content.push(ch);
+            self.chop_char();
+        }
+    
+        return Err(LexerError::UnterminatedStringLiteral);
+    }
+
+    /// `lex_escape_sequence`, but in recovery mode a bad escape is recorded and replaced by U+FFFD.
+    fn lex_escape_or_recover(&mut self, escape_start: usize) -> Result<char, LexerError> {
+        match self.lex_escape_sequence() {
+            Ok(c) => Ok(c),
+            Err(e) if self.options.recovery => {
+                self.errors.push((self.location_at(escape_start), e));
+                Ok(char::REPLACEMENT_CHARACTER)
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Lex the escape after `\` up to and including its last char, even when it's invalid.
+    fn lex_escape_sequence(&mut self) -> Result<char, LexerError> {
+        // https://en.wikipedia.org/wiki/Escape_sequences_in_C#Escape_sequences
+        let escape_start: usize = self.cur - 1; // Include the `\` in error messages
+        let first: char = self.get_char().unwrap();
+        self.chop_char();
+
+        match first {
+            // `\nnn`: at most three octal digits, `"\1234"` is `S4`
+            '0'..='7' => {
+                let digits_start: usize = self.cur - 1;
+                let mut count: usize = 1;
+                while count < 3 && matches!(self.get_char(), Some('0'..='7')) {
+                    self.chop_char();
+                    count += 1;
+                }
+
+                // Digits (and errors) go through `spliced_text`, a `\` + newline may sit between them
+                let value: u32 = self.spliced_text(digits_start, self.cur).chars()
+                    .filter_map(|c| c.to_digit(8))
+                    .fold(0, |value, digit| value * 8 + digit);
+                if value > 0xFF {
+                    return Err(LexerError::EscapeOutOfRange(self.spliced_text(escape_start, self.cur).into_owned()));
+                }
+                return Ok(char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER));
+            },
+
+            // `\xhh…`: as many hex digits as there are
+            'x' => {
+                let digits_start: usize = self.cur;
+                self.consume_while(|c| c.is_ascii_hexdigit());
+                let digits: Cow<str> = self.spliced_text(digits_start, self.cur);
+
+                if digits.is_empty() { return Err(LexerError::MissingDigits("\\x".to_string())); }
+                return match u32::from_str_radix(&digits, 16) {
+                    Ok(value) if value <= 0xFF => Ok(char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER)),
+                    _ => Err(LexerError::EscapeOutOfRange(self.spliced_text(escape_start, self.cur).into_owned())),
+                };
+            },
+
+            // `\uhhhh` and `\Uhhhhhhhh`: exactly 4 or 8 hex digits
+            'u' | 'U' => {
+                let len: usize = if first == 'u' { 4 } else { 8 };
+                let digits_start: usize = self.cur;
+                let mut count: usize = 0;
+                while count < len && self.get_char().is_some_and(|c| c.is_ascii_hexdigit()) {
+                    self.chop_char();
+                    count += 1;
+                }
+
+                let seq: String = self.spliced_text(escape_start, self.cur).into_owned();
+                if count < len {
+                    return Err(LexerError::IncompleteUniversalCharacterName(seq));
+                }
+                let value: u32 = self.spliced_text(digits_start, self.cur).chars()
+                    .filter_map(|c| c.to_digit(16))
+                    .fold(0, |value, digit| value * 16 + digit);
+                return char::from_u32(value).ok_or(LexerError::EscapeOutOfRange(seq));
+            },
+
+            _ => {},
+        }
+
+        return Ok(
+            match first {
+                'a' => 0x07 as char, // Alert (Beep, Bell) - Added in C89
+                'b' => 0x08 as char, // Backspace
+                'f' => 0x0C as char, // Formfeed Page Break
+                'v' => 0x0B as char, // Vertical Tab
+
+                // GNU extensions, unknown in strict mode
+                'e' if self.options.gnu_extensions => 0x1B as char, // Escape character
+                
+                '?' => '?',          // Question mark (used to avoid trigraphs)
+                // https://en.wikipedia.org/wiki/Digraphs_and_trigraphs_(programming)#C
+                
+                'n' => '\n',         // Newline
+                'r' => '\r',         // Carriage Return
+                't' => '\t',         // Horizontal Tab
+                
+                '\'' => '\'',        // '
+                '"' => '"',          // "
+                '\\' => '\\',        // \
+                
+                _ => return Err(LexerError::UnknownEscapeSequence(format!("\\{first}"))),
+            }
+        );
+    }
+
+    fn lex_operator_or_separator(&mut self) -> Result<Token<'src>, LexerError> {
+        let cur_char: char = self.get_char().unwrap();
+        self.chop_char();
+
+        // Maximal munch: always take the longest operator, `<<=` before `<<` before `<`
+        return Ok(
+            match cur_char {
+                '\n' => Token::Newline, // Not trimmed only with `emit_newlines`
+                '\r' => {
+                    self.chop_if('\n'); // `\r\n` is one line end
+                    Token::Newline
+                },
+
+                '(' => Token::OParen,
+                ')' => Token::CParen,
+                '{' => Token::OCurly,
+                '}' => Token::CCurly,
+                '[' => Token::OBracket,
+                ']' => Token::CBracket,
+                ';' => Token::SemiColon,
+                ',' => Token::Comma,
+                '?' => Token::Question,
+                ':' => Token::Colon,
+                '~' => Token::Tilde,
+                '#' if self.options.emit_newlines => if self.chop_if('#') { Token::HashHash } else { Token::Hash },
+
+                '@' if self.options.allow_at_and_backtick => Token::At,
+                '`' if self.options.allow_at_and_backtick => Token::Backtick,
+                '.' if self.starts_with("..") => {
+                    self.chop_char();
+                    self.chop_char();
+                    Token::Ellipsis
+                },
+                '.' if self.options.allow_ranges && self.chop_if('.') => Token::DotDot,
+                '.' => Token::Dot, // `.5` is a number, lexed before getting here
+
+                '+' => {
+                    if self.chop_if('+')      { Token::PlusPlus }
+                    else if self.chop_if('=') { Token::PlusEqual }
+                    else                      { Token::Plus }
+                },
+                '-' => {
+                    if self.chop_if('-')      { Token::MinusMinus }
+                    else if self.chop_if('=') { Token::MinusEqual }
+                    else if self.chop_if('>') { Token::Arrow }
+                    else                      { Token::Minus }
+                },
+                '*' => if self.chop_if('=') { Token::MultiplyEqual } else { Token::Multiply },
+                '/' => if self.chop_if('=') { Token::DivideEqual } else { Token::Divide },
+                '%' => if self.chop_if('=') { Token::ModEqual } else { Token::Mod },
+                '^' => if self.chop_if('=') { Token::XorEqual } else { Token::Xor },
+                '&' => {
+                    if self.chop_if('&')      { Token::AndAnd }
+                    else if self.chop_if('=') { Token::AndEqual }
+                    else                      { Token::And }
+                },
+                '|' => {
+                    if self.chop_if('|')      { Token::OrOr }
+                    else if self.chop_if('=') { Token::OrEqual }
+                    else                      { Token::Or }
+                },
+                '<' => {
+                    if self.chop_if('<') {
+                        if self.chop_if('=') { Token::ShiftLeftEqual } else { Token::ShiftLeft }
+                    } else if self.chop_if('=') {
+                        Token::LessEqual
+                    } else {
+                        Token::Less
+                    }
+                },
+                '>' => {
+                    if self.chop_if('>') {
+                        if self.chop_if('=') { Token::ShiftRightEqual } else { Token::ShiftRight }
+                    } else if self.chop_if('=') {
+                        Token::GreaterEqual
+                    } else {
+                        Token::Greater
+                    }
+                },
+                '=' => if self.chop_if('=') { Token::EqualEqual } else { Token::Equal },
+                '!' => if self.chop_if('=') { Token::NotEqual } else { Token::Not },
+
+                _   => return Err(LexerError::UnknownToken(cur_char)),
+            }
+        );
+    }
+
+    /// Save the position to come back to with `reset`, e.g. before trying one parse
+    /// of an ambiguous construct. Much cheaper than cloning the lexer.
+    ///
+    /// ```
+    /// use mycc::lexer::Lexer;
+    ///
+    /// let mut lexer = Lexer::from("(int) x");
+    /// let mark = lexer.mark();
+    /// let first = (lexer.get_token().unwrap(), lexer.token_location().to_string());
+    /// let second = (lexer.get_token().unwrap(), lexer.token_location().to_string());
+    ///
+    /// lexer.reset(mark);
+    /// assert_eq!((lexer.get_token().unwrap(), lexer.token_location().to_string()), first);
+    /// assert_eq!((lexer.get_token().unwrap(), lexer.token_location().to_string()), second);
+    /// ```
+    pub fn mark(&self) -> LexerPos {
+        self.checkpoint()
+    }
+
+    /// Go back to a position from `mark`. Warnings and recovered errors collected since
+    /// are kept, so lexing the same text again in recovery mode reports them twice.
+    pub fn reset(&mut self, pos: LexerPos) {
+        self.restore(pos);
+        self.peeked = None;
+    }
+
+    fn checkpoint(&self) -> LexerPos {
+        LexerPos { cur: self.cur, token_start: self.token_start }
+    }
+
+    fn restore(&mut self, checkpoint: LexerPos) {
+        self.cur = checkpoint.cur;
+        self.token_start = checkpoint.token_start;
+    }
+
+    fn chop_if(&mut self, expected: char) -> bool {
+        if self.get_char() == Some(expected) {
+            self.chop_char();
+            return true;
+        }
+        false
+    }
+
+    fn consume_while<P>(&mut self, predicate: P) where P: Fn(char) -> bool {
+        loop {
+            let pos: usize = self.skip_splices(self.cur);
+            match self.source[pos..].chars().next() {
+                Some(c) if predicate(c) => self.cur = pos + c.len_utf8(),
+                _ => return,
+            }
+        }
+    }
+        
+    fn is_empty(&self) -> bool {
+        self.skip_splices(self.cur) >= self.source.len()
+    }
+
+    fn chop_char(&mut self) {
+        let pos: usize = self.skip_splices(self.cur);
+        if let Some(c) = self.source[pos..].chars().next() {
+            self.cur = pos + c.len_utf8();
+        }
+    }
+
+    fn starts_with(&self, prefix: &str) -> bool {
+        // A splice can only hide in the text if there's a `\` before where it stops matching
+        let rest: &str = &self.source[self.cur..];
+        if rest.starts_with(prefix) { return true; }
+        if !rest.bytes().take(prefix.len()).any(|b| b == b'\\') { return false; }
+
+        let mut pos: usize = self.cur;
+        for expected in prefix.chars() {
+            pos = self.skip_splices(pos);
+            match self.source[pos..].chars().next() {
+                Some(c) if c == expected => pos += c.len_utf8(),
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// Skip any `\` + newline at `pos`: the lines they join are one line to the lexer
+    /// (translation phase 2), but the source and its locations are left untouched.
+    #[inline]
+    fn skip_splices(&self, mut pos: usize) -> usize {
+        let bytes: &[u8] = self.source.as_bytes();
+        while bytes.get(pos) == Some(&b'\\') {
+            pos += match (bytes.get(pos + 1), bytes.get(pos + 2)) {
+                (Some(b'\r'), Some(b'\n')) => 3,
+                (Some(b'\n' | b'\r'), _)   => 2,
+                _                          => break,
+            };
+        }
+        pos
+    }
+
+    /// Source text between two offsets with splices removed, borrowed unless there were any.
+    fn spliced_text(&self, start: usize, end: usize) -> Cow<'src, str> {
+        let text: &'src str = &self.source[start..end];
+        if !text.contains('\\') { return Cow::Borrowed(text); }
+
+        let mut spliced: String = String::with_capacity(text.len());
+        let mut pos: usize = start;
+        while pos < end {
+            pos = self.skip_splices(pos);
+            if pos >= end { break; }
+            let c: char = self.source[pos..].chars().next().unwrap();
+            spliced.push(c);
+            pos += c.len_utf8();
+        }
+        return Cow::Owned(spliced);
+    }
+
+    fn skip_line_comment(&mut self) {
+        while !self.is_empty() {
+            let c: char = self.get_char().unwrap();
+            if c == '\n' || c == '\r' { break; } // `\` before the newline was spliced away
+            self.chop_char();
+        }
+    }
+
+    fn lex_trivia(&mut self, same_line: bool) -> Result<Vec<Trivia<'src>>, LexerError> {
+        let mut trivia: Vec<Trivia<'src>> = Vec::new();
+
+        loop {
+            let start: usize = self.cur;
+            if self.starts_with("//") {
+                self.skip_line_comment();
+                trivia.push(Trivia::LineComment(&self.source[start..self.cur]));
+                continue;
+            }
+            if self.starts_with("/*") {
+                self.skip_block_comment()?;
+                trivia.push(Trivia::BlockComment(&self.source[start..self.cur]));
+                continue;
+            }
+
+            let newlines: bool = same_line || self.options.emit_newlines;
+            self.consume_while(|c| c.is_whitespace() && !(newlines && matches!(c, '\n' | '\r')));
+            if self.cur == start { return Ok(trivia); }
+            trivia.push(Trivia::Whitespace(&self.source[start..self.cur]));
+        }
+    }
+
+    fn skip_trivia(&mut self) -> Result<(), LexerError> {
+        loop {
+            self.trim_left();
+            if self.starts_with("//") {
+                self.skip_line_comment();
+            } else if self.starts_with("/*") {
+                self.skip_block_comment()?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn skip_block_comment(&mut self) -> Result<(), LexerError> {
+        // Search for each `*` at once instead of chopping char by char, then see if a `/`
+        // follows it, possibly after splices. Comments don't nest: `/* /* */` ends at the first `*/`.
+        let start: usize = self.cur;
+        self.chop_char();
+        self.chop_char(); // Skip `/*`, which may be split by a splice itself
+
+        let mut pos: usize = self.cur;
+        let end: Option<usize> = loop {
+            let Some(star) = self.source[pos..].find('*').map(|i| pos + i) else { break None; };
+            let after: usize = self.skip_splices(star + 1);
+            if self.source.as_bytes().get(after) == Some(&b'/') { break Some(after + 1); }
+            pos = star + 1;
+        };
+
+        self.cur = end.unwrap_or(self.source.len());
+
+        if end.is_none() {
+            self.token_start = start; // Point the error at the comment
+            return Err(LexerError::UnterminatedComment);
+        }
+        return Ok(());
+    }
+
+    fn trim_left(&mut self) {
+        let newlines: bool = self.options.emit_newlines;
+        self.consume_while(|c| c.is_whitespace() && !(newlines && matches!(c, '\n' | '\r')));
+    }
+
+    fn get_char(&self) -> Option<char> {
+        self.source[self.skip_splices(self.cur)..].chars().next()
+    }
+
+    fn peek_char(&self, n: usize) -> Option<char> {
+        let mut pos: usize = self.skip_splices(self.cur);
+        for _ in 0..n {
+            pos = self.skip_splices(pos + self.source[pos..].chars().next()?.len_utf8());
+        }
+        self.source[pos..].chars().next()
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum StreamError {
+    Io(std::io::Error),
+    Lexer(LexerError),
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            StreamError::Io(e)    => write!(f, "{e}"),
+            StreamError::Lexer(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+/// Lexer over any `BufRead` (stdin, a socket, a huge file) that only keeps the lines
+/// it's still lexing in memory. Tokens borrow from that buffer, so each one is only valid
+/// until the next `get_token`; copy out what has to live longer.
+///
+/// ```
+/// use std::io::{BufReader, Cursor};
+/// use mycc::lexer::{StreamLexer, Token};
+///
+/// fn lex_all<R: std::io::BufRead>(mut lexer: StreamLexer<R>) -> Vec<String> {
+///     let mut tokens = Vec::new();
+///     loop {
+///         let token = lexer.get_token().unwrap();
+///         if token == Token::EOF { return tokens; }
+///         let token: String = format!("{token:?}"); // Done with the borrowed token
+///         tokens.push(format!("{token} {}", lexer.token_location()));
+///     }
+/// }
+///
+/// let source = "int some_name = 42; /* a comment\n spanning lines */ char *s =\n \"str\";\n";
+/// let whole = lex_all(StreamLexer::new(Cursor::new(source.as_bytes().to_vec()), "a.c".to_string()));
+/// // 3-byte reads split identifiers, numbers and the comment across chunks
+/// let chunked = lex_all(StreamLexer::new(BufReader::with_capacity(3, source.as_bytes()), "a.c".to_string()));
+/// assert_eq!(whole, chunked);
+/// assert_eq!(whole.len(), 11);
+/// assert_eq!(whole[10], "SemiColon a.c:3:7");
+/// ```
+#[cfg(feature = "std")]
+pub struct StreamLexer<R: std::io::BufRead> {
+    reader: R,
+    filepath: String,
+    options: LexerOptions,  // What every token is lexed with, see `configure`
+    limit: Option<usize>,   // Offset into the whole stream, unlike `cur`
+    buffer: String,         // Whole lines, from the start of the line `cur` is on
+    line_starts: Vec<usize>, // Index of `buffer`, extended as lines are read
+    cur: usize,             // Byte offset into `buffer`
+    rows_dropped: usize,
+    bytes_dropped: usize,
+    reader_done: bool,
+    anchor: StreamPos,      // Last location computed, the next one is counted on from there
+    token_location: Location,
+    warnings: Vec<(Location, LexerWarning)>,
+    errors: Vec<(Location, LexerError)>,
+}
+
+/// Row and column of an offset into `StreamLexer::buffer`.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+struct StreamPos {
+    offset: usize,
+    row: usize,
+    col: usize,
+}
+
+#[cfg(feature = "std")]
+impl StreamPos {
+    /// Count rows and columns on to `offset` the same way as `Lexer::location_at`,
+    /// so lexing a long line costs its length once instead of once per token.
+    fn advance(&mut self, text: &str, offset: usize, tab_width: usize) {
+        if offset < self.offset { *self = StreamPos::default(); }
+
+        let bytes: &[u8] = text.as_bytes();
+        for i in self.offset..offset.min(bytes.len()) {
+            let b: u8 = bytes[i];
+            if b == b'\n' || (b == b'\r' && bytes.get(i + 1) != Some(&b'\n')) {
+                self.row += 1;
+                self.col = 0;
+            } else if b == b'\t' {
+                self.col += tab_width - self.col % tab_width;
+            } else if b & 0xC0 != 0x80 { // Skip UTF-8 continuation bytes
+                self.col += 1;
+            }
+        }
+        self.offset = offset;
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::BufRead> StreamLexer<R> {
+    pub fn new(reader: R, filepath: String) -> Self {
+        let token_location: Location = Location { filepath: filepath.clone(), row: 0, col: 0, tab_width: TAB_WIDTH };
+        Self {
+            reader,
+            filepath,
+            options: LexerOptions::default(),
+            limit: None,
+            buffer: String::new(),
+            line_starts: vec![0],
+            cur: 0,
+            rows_dropped: 0,
+            bytes_dropped: 0,
+            reader_done: false,
+            anchor: StreamPos::default(),
+            token_location,
+            warnings: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Set the `Lexer` options tokens are lexed with, using its builder methods:
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use mycc::lexer::{Keyword, Standard, StreamLexer, Token};
+    ///
+    /// let source = Cursor::new(b"nullptr\n".to_vec());
+    /// let mut lexer = StreamLexer::new(source, "a.c".to_string())
+    ///     .configure(|lexer| lexer.with_standard(Standard::C23).emit_newlines(true));
+    /// assert_eq!(lexer.get_token().unwrap(), Token::Keyword(Keyword::Nullptr));
+    /// assert_eq!(lexer.get_token().unwrap(), Token::Newline);
+    /// ```
+    ///
+    /// A `set_limit` in there counts bytes from the start of the stream, like it would over the whole source.
+    pub fn configure<F>(mut self, configure: F) -> Self where F: FnOnce(Lexer<'static>) -> Lexer<'static> {
+        let mut template: Lexer<'static> = Lexer::new("", String::new());
+        template.options = self.options;
+        template.limit = self.limit;
+
+        let template: Lexer<'static> = configure(template);
+        self.options = template.options;
+        self.limit = template.limit;
+        self
+    }
+
+    /// Warnings of the tokens lexed so far, see `Lexer::warnings`.
+    pub fn warnings(&self) -> &[(Location, LexerWarning)] {
+        &self.warnings
+    }
+
+    /// Errors recovered from so far with `Lexer::recovery`, see `Lexer::errors`.
+    pub fn errors(&self) -> &[(Location, LexerError)] {
+        &self.errors
+    }
+
+    /// Next token, reading more input only when the buffered lines don't hold a complete one.
+    /// `Token::Number` spans are byte offsets into the whole stream.
+    pub fn get_token(&mut self) -> Result<Token<'_>, StreamError> {
+        self.drop_lexed_lines();
+
+        // Whole lines always hold whole tokens, except for a block comment, a line ending
+        // in a splice, or with `concat_strings` a string the next line may continue.
+        // Only the trivia before the token is looked at here, the token itself is lexed once below
+        // (a run of strings twice with `concat_strings`).
+        loop {
+            let spliced: bool = self.buffer.ends_with("\\\n") || self.buffer.ends_with("\\\r\n");
+            if !self.reader_done && (self.cur >= self.buffer.len() || spliced) {
+                self.read_line()?;
+                continue;
+            }
+
+            let mut lexer: Lexer = Self::lexer(self.options, self.buffer_limit(), &self.buffer, &mut self.line_starts, self.cur);
+            let trivia: Result<(), LexerError> = lexer.skip_trivia();
+            let (cur, comment_start, empty): (usize, usize, bool) = (lexer.cur, lexer.token_start, lexer.is_empty());
+            self.line_starts = lexer.line_starts;
+
+            if self.reader_done { break; }
+            match trivia {
+                Err(_) => {
+                    // Unterminated comment: skip to its end before lexing it again
+                    self.cur = comment_start;
+                    self.read_lines_until("*/")?;
+                    continue;
+                },
+                Ok(()) if empty => {
+                    self.cur = cur;
+                    self.read_line()?;
+                    continue;
+                },
+                Ok(()) => self.cur = cur,
+            }
+
+            if self.options.concat_strings && self.string_may_continue() {
+                self.read_line()?;
+                continue;
+            }
+            break;
+        }
+
+        let mut lexer: Lexer = Self::lexer(self.options, self.buffer_limit(), &self.buffer, &mut self.line_starts, self.cur);
+        let token: Result<Token, LexerError> = lexer.get_token();
+        self.cur = lexer.cur;
+        self.line_starts = core::mem::take(&mut lexer.line_starts);
+
+        let tab_width: usize = self.options.tab_width;
+        self.anchor.advance(&self.buffer, lexer.token_start, tab_width);
+        self.token_location = Location {
+            filepath: self.filepath.clone(),
+            row: self.anchor.row + self.rows_dropped,
+            col: self.anchor.col,
+            tab_width,
+        };
+        let (filepath, rows_dropped): (&str, usize) = (&self.filepath, self.rows_dropped);
+        let shift = |location: Location| Location { filepath: filepath.to_string(), row: location.row + rows_dropped, ..location };
+        self.warnings.extend(lexer.warnings.drain(..).map(|(location, warning)| (shift(location), warning)));
+        self.errors.extend(lexer.errors.drain(..).map(|(location, error)| (shift(location), error)));
+
+        return match token {
+            Ok(Token::Number { value, ty, raw }) => {
+                let raw: Span = Span { start: raw.start + self.bytes_dropped, end: raw.end + self.bytes_dropped };
+                Ok(Token::Number { value, ty, raw })
+            },
+            Ok(token) => Ok(token),
+            Err(e) => Err(StreamError::Lexer(e)),
+        };
+    }
+
+    /// Location of the last token (or error) returned by `get_token`.
+    pub fn token_location(&self) -> Location {
+        self.token_location.clone()
+    }
+
+    /// A lexer over the buffer at `cur`, lending it the line index so nothing is indexed twice.
+    /// Give the index back with `line_starts` once done. Building one allocates nothing,
+    /// so its locations have no filepath: `get_token` fills it in on the ones it keeps.
+    fn lexer<'buf>(
+        options: LexerOptions,
+        limit: Option<usize>,
+        buffer: &'buf str,
+        line_starts: &mut Vec<usize>,
+        cur: usize,
+    ) -> Lexer<'buf> {
+        Lexer {
+            source: buffer,
+            filepath: String::new(),
+            cur,
+            token_start: cur,
+            line_starts: core::mem::take(line_starts),
+            options,
+            limit,
+            warnings: Vec::new(),
+            errors: Vec::new(),
+            peeked: None,
+        }
+    }
+
+    /// `limit` as an offset into `buffer`.
+    fn buffer_limit(&self) -> Option<usize> {
+        self.limit.map(|limit| limit.saturating_sub(self.bytes_dropped))
+    }
+
+    /// Whether the token at `cur` is a run of strings followed by nothing but trivia up to the end
+    /// of the buffer, so the next line may hold another string for `concat_strings` to merge.
+    fn string_may_continue(&mut self) -> bool {
+        let mut lexer: Lexer = Self::lexer(self.options, self.buffer_limit(), &self.buffer, &mut self.line_starts, self.cur);
+        let string: bool = matches!(lexer.get_token(), Ok(Token::String { .. }));
+        let rest_is_trivia: bool = string && (lexer.skip_trivia().is_err() || lexer.is_empty());
+        self.line_starts = lexer.line_starts;
+        rest_is_trivia
+    }
+
+    fn read_line(&mut self) -> Result<(), StreamError> {
+        let len: usize = self.buffer.len();
+        let read: usize = self.reader.read_line(&mut self.buffer).map_err(StreamError::Io)?;
+        if read == 0 { self.reader_done = true; }
+
+        // Index only what was just read, like `Lexer::index_line_starts`
+        let bytes: &[u8] = self.buffer.as_bytes();
+        for i in len..bytes.len() {
+            let b: u8 = bytes[i];
+            if b == b'\n' || (b == b'\r' && bytes.get(i + 1) != Some(&b'\n')) { self.line_starts.push(i + 1); }
+        }
+        Ok(())
+    }
+
+    /// Read lines up to and including one containing `needle`, or to the end of the input.
+    fn read_lines_until(&mut self, needle: &str) -> Result<(), StreamError> {
+        loop {
+            let len: usize = self.buffer.len();
+            self.read_line()?;
+            if self.reader_done || self.buffer[len..].contains(needle) { return Ok(()); }
+        }
+    }
+
+    /// Forget the lines before the one `cur` is on.
+    fn drop_lexed_lines(&mut self) {
+        let row: usize = self.line_starts.partition_point(|&start| start <= self.cur) - 1;
+        let line_start: usize = self.line_starts[row];
+        if line_start == 0 { return; }
+
+        self.buffer.drain(..line_start);
+        self.cur -= line_start;
+        self.line_starts.drain(..row);
+        for start in &mut self.line_starts { *start -= line_start; }
+        self.rows_dropped += row;
+        self.bytes_dropped += line_start;
+        self.anchor = StreamPos::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(source: &str) -> Vec<Token<'_>> {
+        Lexer::from(source).tokenize().unwrap()
+    }
+
+    fn id(name: &str) -> Token<'_> {
+        Token::ID(Cow::Borrowed(name))
+    }
+
+    fn string(value: &str) -> Token<'static> {
+        Token::String { value: value.to_string(), encoding: Encoding::Plain }
+    }
+
+    /// 1-based `(row, col)` of the next token, as displayed.
+    fn next_position(lexer: &mut Lexer) -> (usize, usize) {
+        lexer.get_token().unwrap();
+        let location: Location = lexer.token_location();
+        (location.row + 1, location.col + 1)
+    }
+
+    fn int(value: u64, ty: NumType, start: usize, end: usize) -> Token<'static> {
+        Token::Number { value: NumValue::Int(value), ty, raw: Span { start, end } }
+    }
+
+    #[test]
+    fn implicit_octal_warns() {
+        let mut lexer: Lexer = Lexer::from("013").warn_implicit_octal(true);
+        assert_eq!(lexer.tokenize().unwrap(), [int(11, NumType::Int, 0, 3), Token::EOF]);
+        assert!(matches!(lexer.warnings(), [(_, LexerWarning::ImplicitOctal(text))] if text == "013"));
+
+        let mut lexer: Lexer = Lexer::from("0").warn_implicit_octal(true);
+        assert_eq!(lexer.tokenize().unwrap(), [int(0, NumType::Int, 0, 1), Token::EOF]);
+        assert!(lexer.warnings().is_empty());
+
+        let mut lexer: Lexer = Lexer::from("013");
+        lexer.tokenize().unwrap();
+        assert!(lexer.warnings().is_empty());
+    }
+
+    #[test]
+    fn equal_then_not_is_two_operators() {
+        assert_eq!(
+            Lexer::from("a=!b").tokenize().unwrap(),
+            [Token::ID("a".into()), Token::Equal, Token::Not, Token::ID("b".into()), Token::EOF],
+        );
+    }
+
+    #[test]
+    fn newline_ends_define_with_trailing_comment() {
+        let one: Token = int(1, NumType::Int, 10, 11);
+        for source in ["#define X 1 // c\nint y;", "#define X 1 /* c */\nint y;"] {
+            let tokens: Vec<Token> = Lexer::from(source).emit_newlines(true).tokenize().unwrap();
+            assert_eq!(tokens, [
+                Token::Hash, Token::ID("define".into()), Token::ID("X".into()), one.clone(), Token::Newline,
+                Token::Keyword(Keyword::Int), Token::ID("y".into()), Token::SemiColon, Token::EOF,
+            ], "{source}");
+        }
+
+        // A newline inside a block comment doesn't end the directive
+        let tokens: Vec<Token> = Lexer::from("#define X 1 /* c\n */ int y;").emit_newlines(true).tokenize().unwrap();
+        assert!(!tokens.contains(&Token::Newline));
+
+        assert_eq!(Lexer::from("a ## b").emit_newlines(true).tokenize().unwrap(), [Token::ID("a".into()), Token::HashHash, Token::ID("b".into()), Token::EOF]);
+        assert_eq!(Lexer::from("#define").tokenize(), Err(LexerError::UnknownToken('#')));
+    }
+
+    #[test]
+    fn ranges_and_ellipsis() {
+        let tokens: Vec<Token> = Lexer::from("1..5").allow_ranges(true).tokenize().unwrap();
+        assert_eq!(tokens, [int(1, NumType::Int, 0, 1), Token::DotDot, int(5, NumType::Int, 3, 4), Token::EOF]);
+
+        let tokens: Vec<Token> = Lexer::from("1...5").allow_ranges(true).tokenize().unwrap();
+        assert_eq!(tokens, [int(1, NumType::Int, 0, 1), Token::Ellipsis, int(5, NumType::Int, 4, 5), Token::EOF]);
+
+        for ranges in [false, true] {
+            let tokens: Vec<Token> = Lexer::from("int f(int, ...);").allow_ranges(ranges).tokenize().unwrap();
+            assert_eq!(tokens[5], Token::Ellipsis);
+            assert_eq!(tokens.len(), 9);
+        }
+
+        let float = |value: f64, start: usize, end: usize| Token::Number { value: NumValue::Float(value), ty: NumType::Double, raw: Span { start, end } };
+        assert_eq!(Lexer::from("1..5").tokenize().unwrap(), [float(1.0, 0, 2), float(0.5, 2, 4), Token::EOF]);
+    }
+
+    #[test]
+    fn hex_floats() {
+        let value = |source: &str| match Lexer::from(source).get_token().unwrap() {
+            Token::Number { value: NumValue::Float(value), ty, .. } => (value, ty),
+            token => panic!("{source}: {token:?}"),
+        };
+        assert_eq!(value("0x1p3"), (8.0, NumType::Double));
+        assert_eq!(value("0x1.8p-2"), (0.375, NumType::Double));
+        assert_eq!(value("0X.8P1f"), (1.0, NumType::Float));
+        assert_eq!(value("0xA.p+0L"), (10.0, NumType::LongDouble));
+
+        assert_eq!(Lexer::from("0x1.8").get_token(), Err(LexerError::MissingBinaryExponent("0x1.8".to_string())));
+        assert_eq!(Lexer::from("0x1p").get_token(), Err(LexerError::EmptyExponent));
+        assert_eq!(Lexer::from("0x.p1").get_token(), Err(LexerError::MissingDigits("0x".to_string())));
+        assert_eq!(Lexer::from("0xp1").get_token(), Err(LexerError::MissingDigits("0x".to_string())));
+        assert_eq!(Lexer::from("0X.P1").get_token(), Err(LexerError::MissingDigits("0X".to_string())));
+        assert_eq!(normalize_float_literal("0x1.8P-2"), "0x1.8p-2");
+    }
+
+    #[test]
+    fn lone_cr_ends_lines() {
+        for source in ["x\ry", "x\r\ny", "x // c\ry"] {
+            let tokens: Vec<Token> = Lexer::from(source).emit_newlines(true).tokenize().unwrap();
+            assert_eq!(tokens, [Token::ID("x".into()), Token::Newline, Token::ID("y".into()), Token::EOF], "{source:?}");
+        }
+
+        // Recovery resyncs at the line end and leaves the `\r` out of the value
+        let plain = |value: &str| Token::String { value: value.to_string(), encoding: Encoding::Plain };
+        for source in ["\"ab\r\nx", "\"ab\rx"] {
+            let mut lexer: Lexer = Lexer::from(source).recovery(true);
+            assert_eq!(lexer.tokenize().unwrap(), [plain("ab"), Token::ID("x".into()), Token::EOF], "{source:?}");
+            assert_eq!(lexer.errors().len(), 1);
+        }
+
+        assert_eq!(Lexer::from("'\r'").get_token(), Err(LexerError::UnterminatedCharLiteral));
+    }
+
+    #[test]
+    fn locations_after_crlf_and_lone_cr() {
+        for source in ["int\r\n  x", "int\r  x", "int\n  x"] {
+            let mut lexer: Lexer = Lexer::from(source);
+            lexer.get_token().unwrap();
+            assert_eq!(lexer.get_token().unwrap(), Token::ID("x".into()));
+            let location: Location = lexer.token_location();
+            assert_eq!((location.row, location.col), (1, 2), "{source:?}");
+        }
+
+        // `\r` in a string is kept but still ends the line for locations
+        let mut lexer: Lexer = Lexer::from("\"a\rb\" x");
+        assert_eq!(lexer.get_token().unwrap(), Token::String { value: "a\rb".to_string(), encoding: Encoding::Plain });
+        lexer.get_token().unwrap();
+        let location: Location = lexer.token_location();
+        assert_eq!((location.row, location.col), (1, 3));
+    }
+
+    #[test]
+    fn splices() {
+        let plain = |value: &str| Token::String { value: value.to_string(), encoding: Encoding::Plain };
+
+        let mut lexer: Lexer = Lexer::from("in\\\nt x\\\r\ny;");
+        assert_eq!(lexer.tokenize().unwrap(), [Token::Keyword(Keyword::Int), Token::ID("xy".into()), Token::SemiColon, Token::EOF]);
+        let mut lexer: Lexer = Lexer::from("in\\\nt x\\\r\ny;");
+        lexer.get_token().unwrap();
+        lexer.get_token().unwrap();
+        assert_eq!(lexer.token_location().row, 1); // Where `x` starts
+
+        assert_eq!(Lexer::from("\"ab\\\ncd\"").tokenize().unwrap(), [plain("abcd"), Token::EOF]);
+        assert_eq!(Lexer::from("a \\ b").tokenize(), Err(LexerError::UnknownToken('\\')));
+
+        // Escapes split by a splice
+        assert_eq!(Lexer::from("\"\\1\\\n2\"").get_token().unwrap(), plain("\n"));
+        assert_eq!(Lexer::from("\"\\u00\\\ne9\"").get_token().unwrap(), plain("é"));
+        assert_eq!(Lexer::from("\"\\x4\\\n1\"").get_token().unwrap(), plain("A"));
+        assert_eq!(Lexer::from("\"\\4\\\n00\"").get_token(), Err(LexerError::EscapeOutOfRange("\\400".to_string())));
+
+        // Comments
+        assert_eq!(Lexer::from("/* a *\\\n/ int x;").tokenize().unwrap().len(), 4);
+        assert_eq!(Lexer::from("/\\\n*/ int x; */ y").tokenize().unwrap(), [Token::ID("y".into()), Token::EOF]);
+        assert_eq!(Lexer::from("// foo \\\nbar();\nx").tokenize().unwrap(), [Token::ID("x".into()), Token::EOF]);
+        assert_eq!(Lexer::from("/* a */ x").tokenize().unwrap(), [Token::ID("x".into()), Token::EOF]);
+        assert_eq!(Lexer::from("/*/ x").tokenize(), Err(LexerError::UnterminatedComment));
+    }
+
+    /// Every token with its location, through `Lexer` and through `StreamLexer` reading `chunk` bytes at a time.
+    #[cfg(feature = "std")]
+    fn lex_both(source: &'static str, chunk: usize, configure: fn(Lexer<'static>) -> Lexer<'static>) -> (Vec<String>, Vec<String>) {
+        let mut lexer: Lexer = configure(Lexer::new(source, "a.c".to_string()));
+        let mut expected: Vec<String> = Vec::new();
+        loop {
+            let token = lexer.get_token();
+            expected.push(format!("{token:?} {}", lexer.token_location()));
+            if !matches!(token, Ok(ref t) if *t != Token::EOF) { break; }
+        }
+
+        let reader = std::io::BufReader::with_capacity(chunk, source.as_bytes());
+        let mut stream = StreamLexer::new(reader, "a.c".to_string()).configure(configure);
+        let mut actual: Vec<String> = Vec::new();
+        loop {
+            let token = stream.get_token().map_err(|e| match e {
+                StreamError::Lexer(e) => e,
+                StreamError::Io(e) => panic!("{e}"),
+            });
+            let done: bool = !matches!(token, Ok(ref t) if *t != Token::EOF);
+            let token: String = format!("{token:?}"); // Spans are offsets into the whole input like `Lexer`'s
+            actual.push(format!("{token} {}", stream.token_location()));
+            if done { break; }
+        }
+        (expected, actual)
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn stream_matches_lexer() {
+        let sources: &[&str] = &[
+            "int some_name = 42; /* a comment\n spanning lines */ char *s =\n \"str\";\n",
+            "a\r\nb\rc\n\td /* x *\\\n/ e\n",
+            "in\\\nt x = 0x1p3 + \"a\\\nb\";\n// c \\\n d\ny",
+            "\"a\"\n\n\"b\" /* x\n */ \"c\" x \"d\"",
+            "x = 1;\n/* never closed\n",
+        ];
+        let configs: [fn(Lexer<'static>) -> Lexer<'static>; 4] = [
+            |lexer| lexer,
+            |lexer| lexer.emit_newlines(true).tab_width(4),
+            |lexer| lexer.concat_strings(true),
+            |mut lexer| {
+                lexer.set_limit(30); // Past the first line, so after some lines are dropped
+                lexer
+            },
+        ];
+        for source in sources {
+            for configure in configs {
+                for chunk in [1, 2, 3, 7, 64] {
+                    let (expected, actual) = lex_both(source, chunk, configure);
+                    assert_eq!(expected, actual, "{source:?}, {chunk}-byte reads");
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn stream_warnings_and_errors_have_stream_rows() {
+        let source: &[u8] = b"x;\ny = 013;\n\"ab\\q\";\n";
+        let mut stream = StreamLexer::new(std::io::BufReader::with_capacity(2, source), "a.c".to_string())
+            .configure(|lexer| lexer.warn_implicit_octal(true).recovery(true));
+        while stream.get_token().unwrap() != Token::EOF {}
+
+        assert_eq!(stream.warnings().len(), 1);
+        assert_eq!(stream.warnings()[0].0.to_string(), "a.c:2:5");
+        assert_eq!(stream.errors().len(), 1);
+        assert_eq!(stream.errors()[0].0.to_string(), "a.c:3:4");
+    }
+
+    #[test]
+    fn location_at_maps_offsets_back() {
+        let source: &str = "int x;\n\tfoo;\nbar;";
+        let lexer: Lexer = Lexer::from(source);
+        let third_line: Location = lexer.location_at(source.find("bar").unwrap());
+        assert_eq!((third_line.row + 1, third_line.col + 1), (3, 1));
+
+        let foo: Location = lexer.location_at(source.find("foo").unwrap());
+        assert_eq!((foo.row + 1, foo.col + 1), (2, 9)); // Tab expanded to 8
+        assert_eq!(lexer.location_at(1000).row, 2); // Past the end clamps to it
+    }
+
+    #[test]
+    fn backslash_newline_continues_line_comment() {
+        assert_eq!(lex("// foo \\\nbar();\nx"), [id("x"), Token::EOF]);
+        assert_eq!(lex("// foo \\ bar\nx"), [id("x"), Token::EOF]);
+    }
+
+    #[test]
+    fn number_types() {
+        let number = |source: &str| match Lexer::from(source).get_token().unwrap() {
+            Token::Number { value, ty, .. } => (value, ty),
+            token => panic!("{source}: {token:?}"),
+        };
+        assert_eq!(number("10ULL"), (NumValue::Int(10), NumType::ULongLong));
+        assert_eq!(number("3.14f"), (NumValue::Float("3.14".parse().unwrap()), NumType::Float));
+        assert_eq!(number("10u"), (NumValue::Int(10), NumType::UInt));
+        assert_eq!(number("10l"), (NumValue::Int(10), NumType::Long));
+        assert_eq!(number("10LL"), (NumValue::Int(10), NumType::LongLong));
+        assert_eq!(number("1.5L"), (NumValue::Float(1.5), NumType::LongDouble));
+        assert_eq!(number("0xFFFFFFFF"), (NumValue::Int(0xFFFF_FFFF), NumType::UInt));
+        assert_eq!(number("4294967295"), (NumValue::Int(4_294_967_295), NumType::Long));
+
+        let mut lexer: Lexer = Lexer::from("x = 10ULL;");
+        assert_eq!(lexer.expect_token(TokenKind::ID).unwrap(), Some(id("x")));
+        assert_eq!(lexer.expect_token(TokenKind::Number).unwrap(), None);
+        assert!(lexer.expect_token(TokenKind::Number).unwrap().is_some());
+    }
+
+    #[test]
+    fn trivia_round_trips() {
+        let source: &str = "/* head */ int  x = 1; // tail\n\n\tchar *s = \"a\\n\"; /* mid\n */ return;\n";
+        let tokens: Vec<TokenWithTrivia> = Lexer::from(source).tokenize_with_trivia().unwrap();
+
+        let mut rebuilt: String = String::new();
+        for token in &tokens {
+            token.leading.iter().for_each(|trivia| rebuilt.push_str(trivia.text()));
+            rebuilt.push_str(&source[token.span.start..token.span.end]);
+            token.trailing.iter().for_each(|trivia| rebuilt.push_str(trivia.text()));
+        }
+        assert_eq!(rebuilt, source);
+
+        assert_eq!(tokens[0].leading, [Trivia::BlockComment("/* head */"), Trivia::Whitespace(" ")]);
+        let semicolon: &TokenWithTrivia = &tokens[4];
+        assert_eq!(semicolon.trailing, [Trivia::Whitespace(" "), Trivia::LineComment("// tail")]);
+    }
+
+    #[test]
+    fn mismatched_brackets() {
+        let mismatches: Vec<BracketMismatch> = Lexer::from("int f() { return; )").match_brackets().unwrap();
+        assert_eq!(mismatches.len(), 1);
+        let BracketMismatch::Mismatched { open: (open, open_loc), close: (close, close_loc) } = &mismatches[0] else {
+            panic!("{mismatches:?}");
+        };
+        assert_eq!((*open, open_loc.col), ('{', 8));
+        assert_eq!((*close, close_loc.col), (')', 18));
+        assert_eq!(mismatches[0].message(), "')' does not match '{' opened at <anonymous>:1:9");
+
+        let mismatches: Vec<BracketMismatch> = Lexer::from(") ( [ ]").match_brackets().unwrap();
+        assert!(matches!(mismatches[..], [BracketMismatch::Unopened { close: (')', _) }, BracketMismatch::Unclosed { open: ('(', _) }]));
+        assert!(Lexer::from("f(a[1], { b });").match_brackets().unwrap().is_empty());
+    }
+
+    #[test]
+    fn c11_keywords() {
+        for (spelling, keyword) in [
+            ("_Noreturn", Keyword::Noreturn),
+            ("_Thread_local", Keyword::ThreadLocal),
+            ("_Atomic", Keyword::Atomic),
+            ("_Alignas", Keyword::Alignas),
+        ] {
+            assert_eq!(Lexer::from(spelling).with_standard(Standard::C11).get_token().unwrap(), Token::Keyword(keyword));
+            assert_eq!(Lexer::from(spelling).with_standard(Standard::C99).get_token().unwrap(), id(spelling));
+        }
+
+        let atomic: Vec<Token> = Lexer::from("_Atomic(int)").tokenize().unwrap();
+        assert_eq!(atomic, [Token::Keyword(Keyword::Atomic), Token::OParen, Token::Keyword(Keyword::Int), Token::CParen, Token::EOF]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn equal_tokens_hash_equal() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let hash = |tokens: &[Token]| {
+            let mut hasher: DefaultHasher = DefaultHasher::new();
+            tokens.hash(&mut hasher);
+            hasher.finish()
+        };
+        // Same tokens at different offsets, so their `raw` spans differ
+        let a: Vec<Token> = lex("x = 1 + \"s\";");
+        let b: Vec<Token> = lex("x  =  1 +  \"s\" ;");
+        let c: Vec<Token> = lex("x = 2 + \"s\";");
+        let d: Vec<Token> = lex("y = 1 + \"s\";");
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        assert_ne!(hash(&a), hash(&c));
+        assert_ne!(hash(&a), hash(&d));
+        assert_ne!(hash(&lex("1")), hash(&lex("1u")));
+    }
+
+    #[test]
+    fn dollar_in_identifiers() {
+        assert_eq!(Lexer::from("a$b").allow_dollar_in_identifiers(true).tokenize().unwrap(), [id("a$b"), Token::EOF]);
+        assert_eq!(Lexer::from("$a").allow_dollar_in_identifiers(true).tokenize().unwrap(), [id("$a"), Token::EOF]);
+        assert_eq!(Lexer::from("a$b").tokenize(), Err(LexerError::UnknownToken('$')));
+    }
+
+    #[test]
+    fn normalizes_float_literals() {
+        assert_eq!(normalize_float_literal(".5"), "0.5");
+        assert_eq!(normalize_float_literal("5."), "5.0");
+        assert_eq!(normalize_float_literal("1E3"), "1e3");
+        assert_eq!(normalize_float_literal("2.5E-3F"), "2.5e-3f");
+        let style: FloatStyle = FloatStyle { uppercase_exponent: true, uppercase_suffix: true };
+        assert_eq!(normalize_float_literal_with("1e3f", style), "1E3F");
+    }
+
+    #[test]
+    fn assignment_operators() {
+        let assignments: Vec<Token> = lex("= += -= *= /= %= &= |= ^= <<= >>=");
+        assert_eq!(assignments.len(), 12);
+        assert!(assignments[..11].iter().all(Token::is_assignment_operator));
+
+        let others: Vec<Token> = lex("== != <= >= << >> + - ! ~ ++ --");
+        assert!(!others.iter().any(Token::is_assignment_operator));
+    }
+
+    #[test]
+    fn skip_to_ignores_nested_braces() {
+        let mut lexer: Lexer = Lexer::from("a { b; { c; } d; } e; f");
+        lexer.skip_to(|token| *token == Token::SemiColon).unwrap();
+        assert_eq!(lexer.get_token().unwrap(), id("f"));
+
+        let mut lexer: Lexer = Lexer::from("{ ; }");
+        lexer.skip_to(|token| *token == Token::SemiColon).unwrap();
+        assert_eq!(lexer.get_token().unwrap(), Token::EOF);
+    }
+
+    #[test]
+    fn gnu_escapes() {
+        assert_eq!(lex("\"\\e\""), [string("\x1B"), Token::EOF]);
+        assert_eq!(
+            Lexer::from("\"\\e\"").gnu_extensions(false).tokenize(),
+            Err(LexerError::UnknownEscapeSequence("\\e".to_string())),
+        );
+    }
+
+    #[test]
+    fn recovery_collects_every_bad_escape() {
+        let mut lexer: Lexer = Lexer::from("\"\\q\\z\" x").recovery(true);
+        assert_eq!(lexer.tokenize().unwrap(), [string("\u{FFFD}\u{FFFD}"), id("x"), Token::EOF]);
+        let errors: Vec<&LexerError> = lexer.errors().iter().map(|(_, e)| e).collect();
+        assert_eq!(errors, [
+            &LexerError::UnknownEscapeSequence("\\q".to_string()),
+            &LexerError::UnknownEscapeSequence("\\z".to_string()),
+        ]);
+        assert_eq!(lexer.errors()[1].0.col, 3);
+
+        assert!(Lexer::from("\"\\q\\z\"").tokenize().is_err());
+    }
+
+    #[test]
+    fn with_capacity_matches_new() {
+        let source: &str = "int main() {\n\treturn 0x1F + 'a';\n}\n";
+        let spans = |mut lexer: Lexer| -> Vec<(String, String)> {
+            let mut tokens = Vec::new();
+            loop {
+                let spanned: Spanned = lexer.get_spanned_token().unwrap();
+                tokens.push((format!("{:?}", spanned.token), spanned.start.to_string()));
+                if spanned.token == Token::EOF { return tokens; }
+            }
+        };
+        assert_eq!(
+            spans(Lexer::with_capacity(source, "a.c".to_string(), 1000)),
+            spans(Lexer::new(source, "a.c".to_string())),
+        );
+    }
+
+    #[test]
+    fn locations_after_long_comment() {
+        let source: String = format!("/*{}*/\n  x /* a\n\tb */ y", "line\t\u{e9}\n".repeat(500));
+        let mut lexer: Lexer = Lexer::from(source.as_str());
+        assert_eq!(next_position(&mut lexer), (502, 3));
+        assert_eq!(next_position(&mut lexer), (503, 14));
+
+        // The naive way: count every char up to `y`
+        let y: usize = source.rfind('y').unwrap();
+        let row: usize = source[..y].matches('\n').count();
+        let bol: usize = source[..y].rfind('\n').unwrap() + 1;
+        let col: usize = source[bol..y].chars().fold(0, |col, c| if c == '\t' { col + 8 - col % 8 } else { col + 1 });
+        assert_eq!((row + 1, col + 1), (503, 14));
+    }
+
+    #[test]
+    fn unterminated_string_resyncs_at_next_line() {
+        let mut lexer: Lexer = Lexer::from("s = \"abc;\nint x;").recovery(true);
+        assert_eq!(lexer.tokenize().unwrap(), [
+            id("s"), Token::Equal, string("abc;"),
+            Token::Keyword(Keyword::Int), id("x"), Token::SemiColon, Token::EOF,
+        ]);
+        assert_eq!(lexer.errors().len(), 1);
+        assert_eq!(lexer.errors()[0].1, LexerError::UnterminatedStringLiteral);
+
+        assert_eq!(Lexer::from("s = \"abc;\nint x;").tokenize(), Err(LexerError::UnterminatedStringLiteral));
+    }
+
+    #[test]
+    fn invalid_utf8_policies() {
+        let bytes: &[u8] = b"int x; /* \xFF */ int y;";
+
+        assert_eq!(decode_source(bytes, InvalidUtf8Policy::Strict).unwrap_err(), LexerError::InvalidUtf8 { byte_offset: 10 });
+
+        let lossy: DecodedSource = decode_source(bytes, InvalidUtf8Policy::Lossy).unwrap();
+        assert!(lossy.text.contains('\u{FFFD}'));
+        assert!(matches!(lossy.warning, Some(LexerWarning::InvalidUtf8Replaced { byte_offset: 10 })));
+        assert_eq!(Lexer::from(&*lossy.text).tokenize().unwrap().len(), 7);
+
+        let latin1: DecodedSource = decode_source(bytes, InvalidUtf8Policy::Latin1).unwrap();
+        assert!(latin1.text.contains('\u{FF}'));
+        assert!(latin1.warning.is_none());
+        assert_eq!(Lexer::from(&*latin1.text).tokenize().unwrap().len(), 7);
+
+        assert!(matches!(decode_source(b"int x;", InvalidUtf8Policy::Strict).unwrap().text, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn keywords_by_standard() {
+        assert!(is_keyword("inline", Standard::C99));
+        assert!(!is_keyword("inline", Standard::C89));
+        assert!(Lexer::from("").with_standard(Standard::C99).is_keyword("inline"));
+        assert!(!Lexer::from("").with_standard(Standard::C89).is_keyword("inline"));
+        for standard in [Standard::C89, Standard::C99, Standard::C11, Standard::C23] {
+            assert!(!is_keyword("foo", standard));
+        }
+
+        // Agrees with how `lex_id` classifies it
+        assert_eq!(Lexer::from("inline").with_standard(Standard::C89).get_token().unwrap(), id("inline"));
+        assert_eq!(Lexer::from("inline").with_standard(Standard::C99).get_token().unwrap(), Token::Keyword(Keyword::Inline));
+    }
+
+    #[test]
+    fn operator_spans_cover_every_char() {
+        let mut lexer: Lexer = Lexer::from("a <<= b->c");
+        lexer.get_token().unwrap();
+        assert_eq!(lexer.get_token().unwrap(), Token::ShiftLeftEqual);
+        assert_eq!(lexer.token_span(), Span { start: 2, end: 5 });
+        lexer.get_token().unwrap();
+        assert_eq!(lexer.get_token().unwrap(), Token::Arrow);
+        assert_eq!(lexer.token_span(), Span { start: 7, end: 9 });
+    }
+
+    #[test]
+    fn maximal_munch() {
+        assert_eq!(lex("a >>= b"), [id("a"), Token::ShiftRightEqual, id("b"), Token::EOF]);
+        assert_eq!(lex("p->x"), [id("p"), Token::Arrow, id("x"), Token::EOF]);
+        assert_eq!(lex("a||b"), [id("a"), Token::OrOr, id("b"), Token::EOF]);
+        assert_eq!(lex("a<b"), [id("a"), Token::Less, id("b"), Token::EOF]);
+        assert_eq!(lex("a += b"), [id("a"), Token::PlusEqual, id("b"), Token::EOF]);
+        assert_eq!(lex("x---y"), [id("x"), Token::MinusMinus, Token::Minus, id("y"), Token::EOF]);
+        assert_eq!(lex("a<<=>>"), [id("a"), Token::ShiftLeftEqual, Token::ShiftRight, Token::EOF]);
+    }
+
+    #[test]
+    fn char_literals() {
+        let char = |value: char| Token::Char { value, encoding: Encoding::Plain };
+        assert_eq!(lex("'a'"), [char('a'), Token::EOF]);
+        assert_eq!(lex("'\\n'"), [char('\n'), Token::EOF]);
+        assert_eq!(lex("'\\\\'"), [char('\\'), Token::EOF]);
+        assert_eq!(lex("'\\''"), [char('\''), Token::EOF]);
+        assert_eq!(lex("'\\u00e9'"), [char('é'), Token::EOF]);
+
+        assert_eq!(Lexer::from("'a").tokenize(), Err(LexerError::UnterminatedCharLiteral));
+        assert_eq!(Lexer::from("'").tokenize(), Err(LexerError::UnterminatedCharLiteral));
+        assert_eq!(Lexer::from("''").tokenize(), Err(LexerError::EmptyCharLiteral));
+
+        let mut lexer: Lexer = Lexer::from("x = 'ab';");
+        assert_eq!(lexer.tokenize().unwrap()[2], char('b'));
+        assert!(matches!(lexer.warnings(), [(location, LexerWarning::MultiCharCharLiteral(text))] if text == "'ab'" && location.col == 4));
+        let mut lexer: Lexer = Lexer::from("L'\\n\\t'");
+        assert_eq!(lexer.tokenize().unwrap(), [Token::Char { value: '\t', encoding: Encoding::Wide }, Token::EOF]);
+        assert_eq!(lexer.warnings().len(), 1);
+    }
+
+    #[test]
+    fn floats() {
+        let float = |source: &str| match Lexer::from(source).get_token().unwrap() {
+            Token::Number { value: NumValue::Float(value), ty, .. } => (value, ty),
+            token => panic!("{source}: {token:?}"),
+        };
+        assert_eq!(float("3.14"), ("3.14".parse().unwrap(), NumType::Double));
+        assert_eq!(float("45.32f"), (45.32, NumType::Float));
+        assert_eq!(float("1e10"), (1e10, NumType::Double));
+        assert_eq!(float("2.5E-3"), (2.5e-3, NumType::Double));
+        assert_eq!(float("1."), (1.0, NumType::Double));
+        assert_eq!(float(".5"), (0.5, NumType::Double));
+        assert_eq!(lex("42"), [int(42, NumType::Int, 0, 2), Token::EOF]);
+        assert_eq!(Lexer::from("1e").tokenize(), Err(LexerError::EmptyExponent));
+        assert_eq!(Lexer::from("1e+").tokenize(), Err(LexerError::EmptyExponent));
+    }
+
+    #[test]
+    fn integer_radixes() {
+        assert_eq!(lex("0xFF"), [int(255, NumType::Int, 0, 4), Token::EOF]);
+        assert_eq!(lex("0X1f"), [int(31, NumType::Int, 0, 4), Token::EOF]);
+        assert_eq!(lex("0777"), [int(511, NumType::Int, 0, 4), Token::EOF]);
+        assert_eq!(lex("0b1010"), [int(10, NumType::Int, 0, 6), Token::EOF]);
+        assert_eq!(lex("0"), [int(0, NumType::Int, 0, 1), Token::EOF]);
+
+        assert_eq!(Lexer::from("0x").tokenize(), Err(LexerError::MissingDigits("0x".to_string())));
+        assert_eq!(Lexer::from("0xg").tokenize(), Err(LexerError::MissingDigits("0x".to_string())));
+        assert_eq!(Lexer::from("0b12").tokenize(), Err(LexerError::InvalidDigit { digit: '2', radix: 2 }));
+        assert_eq!(Lexer::from("09").tokenize(), Err(LexerError::InvalidDigit { digit: '9', radix: 8 }));
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(lex("a // x\nb"), [id("a"), id("b"), Token::EOF]);
+        assert_eq!(lex("a /* /* */ b"), [id("a"), id("b"), Token::EOF]);
+        assert_eq!(lex("a /* x\ny */ b"), [id("a"), id("b"), Token::EOF]);
+        assert_eq!(Lexer::from("a /* b").tokenize(), Err(LexerError::UnterminatedComment));
+
+        let mut lexer: Lexer = Lexer::from("/* one\ntwo */ x");
+        assert_eq!(next_position(&mut lexer), (2, 8));
+    }
+
+    #[test]
+    fn anonymous_lexer() {
+        let mut lexer: Lexer = Lexer::from("1+1");
+        assert_eq!(lexer.tokenize().unwrap().len(), 4);
+        assert_eq!(lexer.token_location().filepath, ANONYMOUS_FILEPATH);
+        assert_eq!(lexer.token_location().to_string(), "<anonymous>:1:4");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn large_input_lexes_quickly() {
+        // With the old `chars().nth(cur)` this took minutes
+        let source: String = "int some_identifier = 12345; /* comment é */ x += 'a';\n".repeat(20_000);
+        let start = std::time::Instant::now();
+        assert_eq!(Lexer::from(source.as_str()).tokenize().unwrap().len(), 20_000 * 9 + 1);
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    }
+
+    #[test]
+    fn token_positions() {
+        let mut lexer: Lexer = Lexer::from("  foo\nbar");
+        assert_eq!(next_position(&mut lexer), (1, 3));
+        assert_eq!(next_position(&mut lexer), (2, 1));
+
+        let spanned: Spanned = Lexer::from("  foo").get_spanned_token().unwrap();
+        assert_eq!((spanned.start.col, spanned.end.col), (2, 5));
+    }
+
+    #[test]
+    fn peek_then_get() {
+        let mut lexer: Lexer = Lexer::from("a\n  b c");
+        assert_eq!(lexer.get_token().unwrap(), id("a"));
+        assert_eq!(lexer.peek_token().unwrap(), id("b"));
+        assert_eq!(lexer.peek_token().unwrap(), id("b")); // Peeking twice is fine
+        assert_eq!(lexer.get_location().row, 0); // The cursor didn't move
+
+        assert_eq!(next_position(&mut lexer), (2, 3));
+        assert_eq!(lexer.peek_token().unwrap(), id("c"));
+        assert_eq!(lexer.get_token().unwrap(), id("c"));
+        assert_eq!(lexer.get_token().unwrap(), Token::EOF);
+    }
+
+    #[test]
+    fn merged_spans() {
+        let mut lexer: Lexer = Lexer::from("a + b * c");
+        let spans: Vec<Span> = (0..5).map(|_| lexer.get_spanned_token().unwrap().span).collect();
+        let product: Span = Span::merge(spans[2], spans[4]);
+        let sum: Span = Span::merge(spans[0], product);
+        assert_eq!(product, Span { start: 4, end: 9 });
+        assert_eq!(sum, Span { start: 0, end: 9 });
+        assert_eq!(Span::merge(product, spans[0]), sum);
+    }
+
+    #[test]
+    fn c23_nullptr_and_binary() {
+        let mut lexer: Lexer = Lexer::from("nullptr 0b101").with_standard(Standard::C23);
+        assert_eq!(lexer.tokenize().unwrap(), [Token::Keyword(Keyword::Nullptr), int(5, NumType::Int, 8, 13), Token::EOF]);
+        assert!(lexer.warnings().is_empty());
+
+        let mut lexer: Lexer = Lexer::from("nullptr 0b101").with_standard(Standard::C11);
+        assert_eq!(lexer.tokenize().unwrap(), [id("nullptr"), int(5, NumType::Int, 8, 13), Token::EOF]);
+        assert!(matches!(lexer.warnings(), [(_, LexerWarning::BinaryLiteral(text))] if text == "0b101"));
+    }
+
+    #[test]
+    fn numeric_escapes() {
+        assert_eq!(lex("\"\\x41\""), [string("A"), Token::EOF]);
+        assert_eq!(lex("\"\\101\""), [string("A"), Token::EOF]);
+        assert_eq!(lex("\"\\1234\""), [string("S4"), Token::EOF]);
+        assert_eq!(lex("\"\\U0001F600\""), [string("😀"), Token::EOF]);
+
+        assert_eq!(Lexer::from("\"\\x\"").tokenize(), Err(LexerError::MissingDigits("\\x".to_string())));
+        assert_eq!(Lexer::from("\"\\400\"").tokenize(), Err(LexerError::EscapeOutOfRange("\\400".to_string())));
+        assert_eq!(Lexer::from("\"\\x100\"").tokenize(), Err(LexerError::EscapeOutOfRange("\\x100".to_string())));
+        assert_eq!(Lexer::from("\"\\u12\"").tokenize(), Err(LexerError::IncompleteUniversalCharacterName("\\u12".to_string())));
+        assert_eq!(Lexer::from("'\\uD800'").tokenize(), Err(LexerError::EscapeOutOfRange("\\uD800".to_string())));
+    }
+
+    #[test]
+    fn integer_overflow() {
+        assert_eq!(lex("4000000000"), [int(4_000_000_000, NumType::Long, 0, 10), Token::EOF]);
+        assert_eq!(lex("18446744073709551615"), [int(u64::MAX, NumType::ULong, 0, 20), Token::EOF]);
+        assert_eq!(
+            Lexer::from("18446744073709551616").tokenize(),
+            Err(LexerError::IntegerOverflow("18446744073709551616".to_string())),
+        );
+        assert_eq!(
+            Lexer::from("0x10000000000000000").tokenize(),
+            Err(LexerError::IntegerOverflow("0x10000000000000000".to_string())),
+        );
+    }
+
+    #[test]
+    fn case_insensitive_keywords() {
+        for spelling in ["IF", "If", "if"] {
+            assert_eq!(Lexer::from(spelling).case_insensitive_keywords(true).get_token().unwrap(), Token::Keyword(Keyword::If));
+        }
+        assert_eq!(Lexer::from("IF").get_token().unwrap(), id("IF"));
+        assert_eq!(Lexer::from("Foo").case_insensitive_keywords(true).get_token().unwrap(), id("Foo"));
+        assert_eq!(Lexer::from("_BOOL").case_insensitive_keywords(true).get_token().unwrap(), Token::Keyword(Keyword::Bool));
+    }
+
+    #[test]
+    fn numeric_suffix_errors() {
+        let suffix = |source: &str| match Lexer::from(source).get_token() {
+            Err(LexerError::NumericSuffixError { suffix, floating }) => (suffix, floating),
+            token => panic!("{source}: {token:?}"),
+        };
+        assert_eq!(suffix("10LLL"), ("LLL".to_string(), false));
+        assert_eq!(suffix("10xz"), ("xz".to_string(), false));
+        assert_eq!(suffix("10lL"), ("lL".to_string(), false));
+        assert_eq!(suffix("3.14fl"), ("fl".to_string(), true));
+        assert_eq!(
+            LexerError::NumericSuffixError { suffix: "LLL".to_string(), floating: false }.to_string(),
+            "invalid suffix `LLL` on integer constant",
+        );
+    }
+
+    #[test]
+    fn columns_count_chars_not_bytes() {
+        let mut lexer: Lexer = Lexer::from("/* é */ x \"ü\" y");
+        assert_eq!(next_position(&mut lexer), (1, 9));
+        lexer.get_token().unwrap();
+        assert_eq!(next_position(&mut lexer), (1, 15));
+
+        let mut lexer: Lexer = Lexer::from("\tx").tab_width(4);
+        assert_eq!(next_position(&mut lexer), (1, 5));
+        let mut lexer: Lexer = Lexer::from("ab\tx").tab_width(1);
+        lexer.get_token().unwrap();
+        assert_eq!(next_position(&mut lexer), (1, 4));
+    }
+
+    #[test]
+    fn punctuation() {
+        assert_eq!(lex("a ? b : c"), [id("a"), Token::Question, id("b"), Token::Colon, id("c"), Token::EOF]);
+        assert_eq!(lex("s.field"), [id("s"), Token::Dot, id("field"), Token::EOF]);
+        assert_eq!(lex("~x"), [Token::Tilde, id("x"), Token::EOF]);
+        assert_eq!(lex("a[i]"), [id("a"), Token::OBracket, id("i"), Token::CBracket, Token::EOF]);
+        assert_eq!(lex("!x != y"), [Token::Not, id("x"), Token::NotEqual, id("y"), Token::EOF]);
+        assert_eq!(lex("s.5")[1], Token::Number { value: NumValue::Float(0.5), ty: NumType::Double, raw: Span { start: 1, end: 3 } });
+    }
+
+    #[test]
+    fn prefixed_literals() {
+        let prefixed = |value: &str, encoding: Encoding| Token::String { value: value.to_string(), encoding };
+        assert_eq!(lex("L\"hi\""), [prefixed("hi", Encoding::Wide), Token::EOF]);
+        assert_eq!(lex("u8\"hi\""), [prefixed("hi", Encoding::U8), Token::EOF]);
+        assert_eq!(lex("u\"hi\" U\"hi\""), [prefixed("hi", Encoding::U16), prefixed("hi", Encoding::U32), Token::EOF]);
+        assert_eq!(lex("L'x'"), [Token::Char { value: 'x', encoding: Encoding::Wide }, Token::EOF]);
+        assert_eq!(lex("u8x"), [id("u8x"), Token::EOF]);
+        assert_eq!(lex("L x"), [id("L"), id("x"), Token::EOF]);
     }
 }
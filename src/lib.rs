@@ -0,0 +1,12 @@
+#![no_std]
+#![allow(clippy::needless_return)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod diagnostic;
+pub mod driver;
+pub mod lexer;
+pub mod lint;
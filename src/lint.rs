@@ -0,0 +1,160 @@
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{Keyword, Location, Token};
+
+/// Warn about `if (x = 0)` where `==` was probably intended.
+/// An assignment directly inside the condition of `if`/`while`/`for` warns,
+/// an extra pair of parentheses `if ((x = 0))` says it's on purpose.
+/// Takes the tokens `driver::check` lexed (directives left out), `-Wparentheses` turns it on there.
+// TODO: walk the AST instead of tokens once there is a parser
+pub fn assignment_in_condition(tokens: &[(Token, Location)]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for (i, (token, _)) in tokens.iter().enumerate() {
+        // Which `;`-separated clause inside the parentheses is the condition
+        let condition_clause: usize = match token {
+            Token::Keyword(Keyword::If) | Token::Keyword(Keyword::While) => 0,
+            Token::Keyword(Keyword::For)                                 => 1,
+            _ => continue,
+        };
+        if !matches!(tokens.get(i + 1), Some((Token::OParen, _))) { continue; }
+
+        let mut depth: usize = 1;
+        let mut clause: usize = 0;
+        for (token, location) in &tokens[i + 2..] {
+            match token {
+                Token::OParen => depth += 1,
+                Token::CParen => {
+                    depth -= 1;
+                    if depth == 0 { break; }
+                },
+                Token::SemiColon if depth == 1 => clause += 1,
+                Token::Equal if depth == 1 && clause == condition_clause => {
+                    diagnostics.push(Diagnostic::warning(
+                        location.clone(),
+                        "suggest parentheses around assignment used as truth value".to_string(),
+                    ));
+                },
+                _ => {},
+            }
+        }
+    }
+
+    return diagnostics;
+}
+
+/// Warn about repeated qualifiers (`const const int`) and reject conflicting
+/// specifiers (`signed unsigned`, `short long`, `long long long`).
+/// Only keywords right next to each other are compared, `*` starts over: `const int *const p` is fine.
+/// Takes the tokens `driver::check` lexed, `-Wduplicate-decl-specifier` turns it on there.
+// TODO: check the specifiers the declaration parser collected once there is one
+pub fn duplicate_specifiers(tokens: &[(Token, Location)]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    let mut run: Vec<Keyword> = Vec::new(); // Specifiers seen so far in this declaration
+
+    for (token, location) in tokens {
+        let keyword: Keyword = match *token {
+            Token::Keyword(keyword) if is_specifier(keyword) => keyword,
+            _ => {
+                run.clear();
+                continue;
+            },
+        };
+        let location: Location = location.clone();
+        let count = |k: Keyword| run.iter().filter(|&&seen| seen == k).count();
+
+        let diagnostic: Option<Diagnostic> = match keyword {
+            Keyword::Const | Keyword::Volatile | Keyword::Restrict if count(keyword) > 0 => {
+                Some(Diagnostic::warning(location, format!("duplicate `{}`", keyword.spelling())))
+            },
+            Keyword::Signed | Keyword::Unsigned | Keyword::Short if count(keyword) > 0 => {
+                Some(Diagnostic::error(location, format!("duplicate `{}`", keyword.spelling())))
+            },
+            Keyword::Long if count(Keyword::Long) == 2 => {
+                Some(Diagnostic::error(location, "`long long long` is too long".to_string()))
+            },
+            _ => {
+                let conflict: Option<Keyword> = match keyword {
+                    Keyword::Signed   => Some(Keyword::Unsigned),
+                    Keyword::Unsigned => Some(Keyword::Signed),
+                    Keyword::Short    => Some(Keyword::Long),
+                    Keyword::Long     => Some(Keyword::Short),
+                    _                 => None,
+                };
+                conflict.filter(|&other| count(other) > 0).map(|other| Diagnostic::error(
+                    location,
+                    format!("both `{}` and `{}` in declaration specifiers", other.spelling(), keyword.spelling()),
+                ))
+            },
+        };
+        diagnostics.extend(diagnostic);
+        run.push(keyword);
+    }
+
+    return diagnostics;
+}
+
+fn is_specifier(keyword: Keyword) -> bool {
+    matches!(
+        keyword,
+        Keyword::Const | Keyword::Volatile | Keyword::Restrict
+            | Keyword::Signed | Keyword::Unsigned | Keyword::Short | Keyword::Long
+            | Keyword::Char | Keyword::Int | Keyword::Float | Keyword::Double | Keyword::Void | Keyword::Bool
+            | Keyword::Static | Keyword::Extern | Keyword::Register | Keyword::Auto | Keyword::Typedef | Keyword::Inline
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::string::String;
+
+    use crate::diagnostic::Severity;
+    use crate::lexer::Lexer;
+
+    fn tokens(source: &str) -> Vec<(Token<'_>, Location)> {
+        let mut lexer: Lexer = Lexer::new(source, "a.c".to_string());
+        let mut tokens: Vec<(Token, Location)> = Vec::new();
+        loop {
+            let token: Token = lexer.get_token().unwrap();
+            if token == Token::EOF { return tokens; }
+            tokens.push((token, lexer.token_location()));
+        }
+    }
+
+    fn assignment_warnings(source: &str) -> usize {
+        assignment_in_condition(&tokens(source)).len()
+    }
+
+    fn specifiers(source: &str) -> Vec<(Severity, String)> {
+        duplicate_specifiers(&tokens(source)).into_iter().map(|d| (d.severity, d.message)).collect()
+    }
+
+    #[test]
+    fn assignment_in_condition_warns() {
+        assert_eq!(assignment_warnings("if (x = 0) {}"), 1);
+        assert_eq!(assignment_warnings("if ((x = 0)) {}"), 0);
+        assert_eq!(assignment_warnings("if (x == 0) {}"), 0);
+        assert_eq!(assignment_warnings("while (x = next()) {}"), 1);
+        assert_eq!(assignment_warnings("for (i = 0; i = n; i++) {}"), 1);
+        assert_eq!(assignment_warnings("x = 0; if (f(x)) {}"), 0);
+
+        let diagnostics: Vec<Diagnostic> = assignment_in_condition(&tokens("\nif (x = 0) {}"));
+        assert_eq!(diagnostics[0].location.to_string(), "a.c:2:7");
+    }
+
+    #[test]
+    fn duplicate_and_conflicting_specifiers() {
+        assert_eq!(specifiers("const const int x;"), [(Severity::Warning, "duplicate `const`".to_string())]);
+        assert_eq!(
+            specifiers("signed unsigned int x;"),
+            [(Severity::Error, "both `signed` and `unsigned` in declaration specifiers".to_string())],
+        );
+        assert!(specifiers("unsigned long int x;").is_empty());
+        assert!(specifiers("const int *const p;").is_empty());
+        assert_eq!(specifiers("long long long x;"), [(Severity::Error, "`long long long` is too long".to_string())]);
+    }
+}
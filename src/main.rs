@@ -1,24 +1,90 @@
-use std::fs;
+use std::{env, fs, process};
 
-pub mod lexer;
+use mycc::diagnostic::TabRender;
+use mycc::driver;
+use mycc::lexer;
 
 const FILEPATH: &str = "./hw.c";
 
+const USAGE: &str = "usage: mycc [--fsyntax-only] [-Wimplicit-octal] [-Wparentheses] [-Wduplicate-decl-specifier] [file.c...]";
+
 fn main() {
     use lexer as clex;
-    
-    let source_code: String = match fs::read_to_string(FILEPATH) {
-        Ok(content) => content,
-        Err(e) => {
-            panic!("{e}");
-        },
-    };
-
-    let mut lexer: clex::Lexer = clex::Lexer::new(&source_code, FILEPATH.to_string());
-    
-    loop {
-        let token = lexer.get_token().unwrap();
-        if token == clex::Token::EOF { break; }
-        println!("{token:?}");
+
+    let mut syntax_only: bool = false;
+    let mut options: driver::Options = driver::Options::default();
+    let mut filepaths: Vec<String> = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--fsyntax-only"    => syntax_only = true,
+            "-Wimplicit-octal"  => options.warn_implicit_octal = true,
+            "-Wparentheses"     => options.warn_assignment_in_condition = true,
+            "-Wduplicate-decl-specifier" => options.warn_duplicate_specifiers = true,
+            option if option.starts_with('-') => {
+                eprintln!("unknown option `{option}`\n{USAGE}");
+                process::exit(1);
+            },
+            _                   => filepaths.push(arg),
+        }
+    }
+    if filepaths.is_empty() { filepaths.push(FILEPATH.to_string()); }
+
+    let sources: Vec<String> = filepaths.iter().map(|filepath| {
+        let content: Vec<u8> = match fs::read(filepath) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("{filepath}: {e}");
+                process::exit(1);
+            },
+        };
+
+        let decoded: clex::DecodedSource = clex::decode_source(&content, clex::InvalidUtf8Policy::Lossy).unwrap();
+        if let Some(warning) = decoded.warning {
+            eprintln!("{filepath}: warning: {warning}");
+        }
+        decoded.text.into_owned()
+    }).collect();
+
+    if syntax_only {
+        let files: Vec<(&str, &str)> = filepaths.iter().map(String::as_str).zip(sources.iter().map(String::as_str)).collect();
+        let mut failed: bool = false;
+
+        for (filepath, result) in driver::check_files(&files, options) {
+            let diagnostics = match result {
+                Ok(warnings) => warnings,
+                Err(diagnostics) => {
+                    failed = true;
+                    diagnostics
+                },
+            };
+            let source_code: &str = files.iter().find(|(path, _)| *path == filepath).unwrap().1;
+            for diagnostic in diagnostics {
+                eprintln!("{}", diagnostic.render(source_code, TabRender::Expand));
+            }
+        }
+
+        if failed { process::exit(1); }
+        return;
+    }
+
+    for (filepath, source_code) in filepaths.into_iter().zip(&sources) {
+        let mut lexer: clex::Lexer = clex::Lexer::new(source_code, filepath)
+            .with_standard(options.standard)
+            .warn_implicit_octal(options.warn_implicit_octal);
+
+        let tokens: Vec<clex::Token> = match lexer.tokenize() {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                eprintln!("{}", lexer.locate(e));
+                process::exit(1);
+            },
+        };
+        for (location, warning) in lexer.warnings() {
+            eprintln!("{location}: warning: {warning}");
+        }
+        for token in tokens {
+            if token == clex::Token::EOF { break; }
+            println!("{token:?}");
+        }
     }
 }
@@ -0,0 +1,72 @@
+//! Compare our token spellings and locations against `clang -Xclang -dump-tokens`.
+//!
+//!     cargo test --features integration --test clang_tokens
+//!
+//! Needs clang on the `PATH` and fails without it, the feature is opt-in so that it actually compares.
+//! clang reports byte columns: tabs are counted as one column here, and fixtures stay ASCII.
+
+#![cfg(feature = "integration")]
+
+use std::fs;
+use std::process::Command;
+
+use mycc::lexer::{Lexer, Token, TokenWithTrivia};
+
+const FIXTURES: &[&str] = &["tests/fixtures/tokens.c"];
+
+/// `(spelling, row, col)`, 1-based like clang.
+type TokenDump = Vec<(String, usize, usize)>;
+
+fn clang_tokens(filepath: &str) -> Option<TokenDump> {
+    let output = Command::new("clang")
+        .args(["-fsyntax-only", "-Xclang", "-dump-tokens", filepath])
+        .output()
+        .ok()?;
+
+    // identifier 'main'	 [LeadingSpace]	Loc=<hw.c:1:5>
+    let dump: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    let mut tokens: TokenDump = Vec::new();
+    for line in dump.lines() {
+        let (Some(open), Some(loc)) = (line.find('\''), line.rfind("Loc=<")) else { continue; };
+        let Some(close) = line[..loc].rfind('\'') else { continue; };
+        if line.starts_with("eof ") { break; }
+
+        let mut position = line[loc..].trim_end_matches('>').rsplit(':');
+        let col: usize = position.next()?.parse().ok()?;
+        let row: usize = position.next()?.parse().ok()?;
+        tokens.push((line[open + 1..close].to_string(), row, col));
+    }
+    Some(tokens)
+}
+
+fn our_tokens(filepath: &str, source: &str) -> TokenDump {
+    let mut lexer: Lexer = Lexer::new(source, filepath.to_string()).tab_width(1);
+    let tokens: Vec<TokenWithTrivia> = match lexer.tokenize_with_trivia() {
+        Ok(tokens) => tokens,
+        Err(e) => panic!("{}", lexer.locate(e)),
+    };
+
+    tokens.iter()
+        .filter(|t| t.token != Token::EOF)
+        .map(|t| {
+            let location = lexer.location_at(t.span.start);
+            (source[t.span.start..t.span.end].to_string(), location.row + 1, location.col + 1)
+        })
+        .collect()
+}
+
+#[test]
+fn fixtures_match_clang() {
+    for fixture in FIXTURES {
+        let Some(expected) = clang_tokens(fixture) else {
+            panic!("{fixture}: couldn't run `clang -Xclang -dump-tokens`, is clang installed?");
+        };
+        let source: String = fs::read_to_string(fixture).unwrap_or_else(|e| panic!("{fixture}: {e}"));
+        let actual: TokenDump = our_tokens(fixture, &source);
+
+        for (i, (ours, theirs)) in actual.iter().zip(&expected).enumerate() {
+            assert_eq!(ours, theirs, "{fixture}: token #{i}");
+        }
+        assert_eq!(actual.len(), expected.len(), "{fixture}: token count");
+    }
+}
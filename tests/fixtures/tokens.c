@@ -0,0 +1,9 @@
+// Fixture for tests/clang_tokens.rs: stick to what the lexer supports
+int answer(int x, int y)
+{
+    int octal = 042;
+    int same = x == y;
+    printf("Hello, \"World\"\n", same, octal);
+	int tabbed = x;
+    return 0;
+}